@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use tokio::{
     signal::{
         self,
@@ -12,21 +13,90 @@ use tracing::{error, info};
 use crate::{
     Service,
     handler::{
-        ActAlarmHandler, AlarmConfirmHandler, DefaultHandler, FarmConfigHandler, HouseSetHandler,
-        SoundpostsHandler, TestAlarm, TestAlarmHandler,
+        ActAlarmHandler, AlarmConfirmHandler, BroadcastHandler, CommandHandler, FarmConfigHandler,
+        HandlerRegistry, HouseSetHandler, QueueDepths, SoundpostsHandler, TestAlarm,
+        TestAlarmHandler,
     },
-    model::{Alarm, TestAlarmConfig},
+    model::{Alarm, Broadcast, TestAlarmConfig},
     mqtt_client::MqttClient,
+    outbox::Outbox,
     player::Soundpost,
-    recorder::Recorder,
-    task::{Cycle, Play, RealTime, WsClient},
+    record_queue::RecordQueue,
+    recorder::{RecordQuery, Recorder},
+    reload::{ConfigReloader, LevelSetter},
+    task::{Cycle, DbMonitor, Heartbeat, Play, RealTime, WsClient},
+    topic::Topics,
 };
 
-pub async fn run(service: Service, config: crate::config::Config) {
-    let (client, eventloop) = MqttClient::new(config.mqtt);
+/// 查询录音索引
+pub fn records(
+    config: crate::config::Config,
+    house: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> anyhow::Result<()> {
+    let parse = |s: Option<String>| -> anyhow::Result<Option<OffsetDateTime>> {
+        match s {
+            Some(s) => Ok(Some(OffsetDateTime::parse(&s, &Rfc3339).map_err(|e| {
+                anyhow::anyhow!("Invalid time: {s}, expect RFC3339, err: {e}")
+            })?)),
+            None => Ok(None),
+        }
+    };
+
+    let query = RecordQuery {
+        house,
+        from: parse(from)?,
+        to: parse(to)?,
+    };
+
+    let recorder = Recorder::new(config.recorder);
+    for meta in recorder.query_records(&query)? {
+        println!("{}", serde_json::to_string(&meta)?);
+    }
+
+    Ok(())
+}
+
+/// 输出最终生效的配置及各项来源
+pub fn print_config(location: &str, json: bool) -> anyhow::Result<()> {
+    for entry in crate::config::Config::effective(location)? {
+        if json {
+            println!("{}", serde_json::to_string(&entry)?);
+        } else {
+            println!("{} = {}  # {}", entry.key, entry.value, entry.source);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run(
+    service: Service,
+    config: crate::config::Config,
+    config_path: String,
+    set_level: Option<LevelSetter>,
+) -> anyhow::Result<()> {
+    let startup_config = config.clone();
+    let topics = Topics::new(&config.topic)
+        .map_err(|e| anyhow::anyhow!("Mqtt topics config invalid: {e}"))?;
+
+    let heartbeat_interval_secs = config.mqtt.heartbeat_interval_secs();
+    let redacted_config = config.redacted();
+    let outbox = Outbox::open(&config.outbox)
+        .map_err(|e| anyhow::anyhow!("Mqtt outbox open failed: {e}"))?;
+    let (client, eventloop) = MqttClient::new(config.mqtt, &topics, outbox.clone())
+        .map_err(|e| anyhow::anyhow!("Mqtt client create failed: {e}"))?;
+    let records = RecordQueue::open(&config.record_queue)
+        .map_err(|e| anyhow::anyhow!("Play record queue open failed: {e}"))?;
+    let events = RecordQueue::open_events(&config.record_queue)
+        .map_err(|e| anyhow::anyhow!("Alarm event queue open failed: {e}"))?;
     {
         let mut service = service.write().await;
+        service.set_record_queue(records.clone(), events.clone());
         service.set_mqtt_client(client.clone());
+        service.set_topics(topics.clone());
+        service.set_cycle_interval_secs(config.alarm.cycle_interval_secs());
     }
 
     let (act_alarm_tx, act_alarm_rx) = channel::<Alarm>(config.queue.act_alarm_size());
@@ -35,9 +105,21 @@ pub async fn run(service: Service, config: crate::config::Config) {
     let (realtime_play_tx, realtime_play_rx) = channel::<Alarm>(config.queue.realtime_play_size());
     let (cycle_play_tx, cycle_play_rx) = channel::<Alarm>(config.queue.cycle_play_size());
     let (ct_tx, ct_rx) = channel::<TestAlarmConfig>(10);
+    let (broadcast_tx, broadcast_rx) = channel::<Broadcast>(config.queue.broadcast_size());
+
+    let mut queues = QueueDepths::default();
+    queues
+        .add("act_alarm", &act_alarm_tx)
+        .add("test_alarm", &test_alarm_tx)
+        .add("cycle_alarm", &cycle_alarm_tx)
+        .add("realtime_play", &realtime_play_tx)
+        .add("cycle_play", &cycle_play_tx)
+        .add("crontab", &ct_tx)
+        .add("broadcast", &broadcast_tx);
 
     let alarm_media_path = config.soundbox.alarm_media_path();
     let test_media_path = config.soundbox.test_media_path();
+    let broadcast_media_dir = config.soundbox.broadcast_media_dir();
     let alarm_media_url = config.soundpost.alarm_media_url();
     let test_media_url = config.soundpost.test_media_url();
     let alarm_min_duration = config.alarm.alarm_min_duration();
@@ -49,15 +131,16 @@ pub async fn run(service: Service, config: crate::config::Config) {
         config.soundpost.api_login_token(),
     );
 
-    let recorder = Recorder::new(
-        config.recorder.record_storage_path(),
-        config.recorder.record_link_path(),
-    );
+    let recorder = Recorder::new(config.recorder.clone());
+    if let Err(e) = recorder.start_monitor() {
+        error!("Record monitor start failed: {e}");
+    }
     let play_serivce = service.clone();
 
     let play = Play::new(
         alarm_media_path,
         test_media_path,
+        broadcast_media_dir,
         alarm_media_url,
         test_media_url,
         alarm_min_duration,
@@ -67,11 +150,17 @@ pub async fn run(service: Service, config: crate::config::Config) {
         soundpost,
         recorder,
         play_serivce,
-    );
+    )
+    .map_err(|e| anyhow::anyhow!("Player create failed: {e}"))?;
     let play_clone = play.clone();
     let play_handle = tokio::spawn(async move {
         play_clone
-            .run(cycle_alarm_tx, realtime_play_rx, cycle_play_rx)
+            .run(
+                cycle_alarm_tx,
+                realtime_play_rx,
+                cycle_play_rx,
+                broadcast_rx,
+            )
             .await;
     });
 
@@ -83,37 +172,44 @@ pub async fn run(service: Service, config: crate::config::Config) {
             .await;
     });
 
-    // ============================= MQTT 消息处理规则链 ===================================
-    let handler = DefaultHandler::default();
-    // 鸡场更新消息
-    type FH = FarmConfigHandler<DefaultHandler>;
-    let play_clone = play.clone();
-    let service_clone = service.clone();
-    let handler = FH::new(play_clone, service_clone).handler(handler);
-
-    // 鸡舍更新消息
-    type HSH = HouseSetHandler<FH>;
-    let service_clone = service.clone();
-    let handler = HSH::new(service_clone).handler(handler);
-
-    // 音柱配置更新
-    type SPH = SoundpostsHandler<HSH>;
-    let service_clone = service.clone();
-    let handler = SPH::new(service_clone).handler(handler);
+    let db_monitor = DbMonitor::new(service.clone(), ct_tx.clone());
 
-    // 报警确认更新
-    type ACH = AlarmConfirmHandler<SPH>;
-    let service_clone = service.clone();
-    let handler = ACH::new(service_clone).handler(handler);
-
-    // 测试报警配置
-    type TAH = TestAlarmHandler<ACH>;
-    let handler = TAH::new(ct_tx).handler(handler);
-
-    // 真实报警消息
-    type AAH = ActAlarmHandler<TAH>;
-    let play_clone = play.clone();
-    let handler = AAH::new(act_alarm_tx, play_clone).handler(handler);
+    // ============================= MQTT 消息处理器 ===================================
+    let mut registry = HandlerRegistry::default();
+    registry
+        // 鸡场更新消息
+        .register(FarmConfigHandler::new(
+            play.clone(),
+            service.clone(),
+            &topics,
+        ))
+        // 鸡舍更新消息
+        .register(HouseSetHandler::new(service.clone(), &topics))
+        // 音柱配置更新
+        .register(SoundpostsHandler::new(service.clone(), &topics))
+        // 报警确认更新
+        .register(AlarmConfirmHandler::new(service.clone(), &topics))
+        // 测试报警配置
+        .register(TestAlarmHandler::new(ct_tx, service.clone(), &topics))
+        // 真实报警消息
+        .register(ActAlarmHandler::new(act_alarm_tx, play.clone(), &topics))
+        // 临时广播通知
+        .register(BroadcastHandler::new(
+            broadcast_tx,
+            play.clone(),
+            service.clone(),
+            &topics,
+        ))
+        // 运行时查询及控制指令
+        .register(CommandHandler::new(
+            service.clone(),
+            play.clone(),
+            client.clone(),
+            queues,
+            db_monitor.clone(),
+            redacted_config,
+            &topics,
+        ));
     // =========================================================================
 
     let test_alarm_service = service.clone();
@@ -122,16 +218,35 @@ pub async fn run(service: Service, config: crate::config::Config) {
         test_alarm.run(test_alarm_tx, ct_rx).await;
     });
 
-    let topics: Vec<String> = vec![
-        crate::TOPIC_ALARM.to_string(),
-        crate::TOPIC_REPUB_ALARM.to_string(),
-        crate::TOPIC_CRONTAB.to_string(),
-    ];
+    let outbox_client = client.clone();
+    let st = shutdown.clone();
+    let outbox_handle = tokio::spawn(async move {
+        outbox_client.flush_outbox(st).await;
+    });
+
+    let record_service = service.clone();
+    let st = shutdown.clone();
+    let record_handle = tokio::spawn(async move {
+        records.flush(record_service, st).await;
+    });
+
+    let event_service = service.clone();
+    let st = shutdown.clone();
+    let event_handle = tokio::spawn(async move {
+        events.flush(event_service, st).await;
+    });
+
+    let persist = service.read().await.persist.clone();
+    let persist_service = service.clone();
+    let st = shutdown.clone();
+    let persist_handle = tokio::spawn(async move {
+        persist.flush(persist_service, st).await;
+    });
 
     let mqtt_shutdown = shutdown.clone();
     let mqtt_subscribe_handle = tokio::spawn(async move {
         if let Err(e) = client
-            .subscribe(eventloop, topics, &handler, mqtt_shutdown.clone())
+            .subscribe(eventloop, &registry, mqtt_shutdown.clone())
             .await
         {
             error!("Mqtt client subscribe failed: {e}");
@@ -148,35 +263,70 @@ pub async fn run(service: Service, config: crate::config::Config) {
     }
 
     let service_clone = service.clone();
-    let cycle_interval_secs = config.alarm.cycle_interval_secs();
     let cycle_handle = tokio::spawn(async move {
-        Cycle::init(cycle_interval_secs, service_clone)
+        Cycle::init(service_clone)
             .await
             .run(cycle_play_tx, cycle_alarm_rx)
             .await;
     });
 
-    let ws = WsClient::new(
-        config.soundpost.api_host(),
-        config.soundpost.ws_username(),
-        config.soundpost.ws_password(),
-        service,
-    )
-    .await
-    .unwrap();
+    let heartbeat = Heartbeat::new(heartbeat_interval_secs, service.clone());
     let st = shutdown.clone();
+    let heartbeat_handle = tokio::spawn(async move {
+        heartbeat.run(st).await;
+    });
+
+    let st = shutdown.clone();
+    let db_monitor_handle = tokio::spawn(async move {
+        db_monitor.run(st).await;
+    });
+
+    let api_host = config.soundpost.api_host();
+    let ws_username = config.soundpost.ws_username();
+    let ws_password = config.soundpost.ws_password();
+    let ws_service = service.clone();
+    let st = shutdown.clone();
+    let soundpost_enabled = config.soundpost.enabled();
     let ws_handle = tokio::spawn(async move {
+        if !soundpost_enabled {
+            return;
+        }
+        // 音柱服务不可用时不影响启动，后台重试登录
+        let ws = tokio::select! {
+            _ = st.notified() => return,
+            ws = WsClient::connect(api_host, ws_username, ws_password, ws_service) => ws,
+        };
         ws.subscribe(st).await;
     });
 
+    let mut reloader = ConfigReloader::new(
+        config_path,
+        &startup_config,
+        service.clone(),
+        play.clone(),
+        set_level,
+    );
+
     #[cfg(unix)]
     let mut term_signal = signal(SignalKind::terminate()).unwrap();
+    #[cfg(unix)]
+    let mut hup_signal = signal(SignalKind::hangup()).unwrap();
 
     let st = shutdown.clone();
-    tokio::select! {
-        _ = signal::ctrl_c() => info!("Received Ctrl+C"),
-        _ = term_signal.recv() => info!("Received SIGTERM"),
-        _ = st.notified() => info!("Some error happend, exit...")
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => info!("Received Ctrl+C"),
+            _ = term_signal.recv() => info!("Received SIGTERM"),
+            _ = hup_signal.recv() => {
+                info!("Received SIGHUP, reload config...");
+                if let Err(e) = reloader.reload().await {
+                    error!("Config reload failed, keep the current config: {e}");
+                }
+                continue;
+            }
+            _ = st.notified() => info!("Some error happend, exit...")
+        }
+        break;
     }
 
     shutdown.notify_waiters();
@@ -185,14 +335,29 @@ pub async fn run(service: Service, config: crate::config::Config) {
     info!("Waitting for player finish the playing...");
     let _ = tokio::join!(
         mqtt_subscribe_handle,
+        outbox_handle,
+        record_handle,
+        event_handle,
+        persist_handle,
         real_time_handle,
         cycle_handle,
         test_alarm_handle,
         ws_handle,
+        heartbeat_handle,
+        db_monitor_handle,
         play_handle
     );
 
+    // 等待队列文件写入完成，退出期间产生的记录及消息在重启后继续处理
+    {
+        let service = service.read().await;
+        service.records.sync().await;
+        service.events.sync().await;
+    }
+    outbox.sync().await;
+
     info!("==================== Alarm player exited ====================");
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1,17 +1,121 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
 use bytes::Bytes;
+use rumqttc::v5::mqttbytes::{QoS, v5::PublishProperties};
+use tracing::info;
+
+use crate::topic;
+
+/// 订阅主题及服务质量
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    /// 主题过滤器，支持通配符及共享订阅
+    pub filter: String,
+    pub qos: QoS,
+    /// 处理失败时的错误回复主题
+    pub reply: Option<String>,
+}
+
+impl Subscription {
+    pub fn new(filter: String) -> Self {
+        Self {
+            filter,
+            qos: QoS::AtLeastOnce,
+            reply: None,
+        }
+    }
+
+    pub fn with_reply(mut self, reply: String) -> Self {
+        self.reply = Some(reply);
+        self
+    }
+
+    pub fn with_qos(mut self, qos: QoS) -> Self {
+        self.qos = qos;
+        self
+    }
+}
 
 /// 消息处理器
-pub trait Handler: Clone + Send + Sync {
-    /// 消息处理
-    fn proc(&self, topic: String, payload: Bytes) -> impl Future<Output = anyhow::Result<()>>;
+#[async_trait]
+pub trait Handler: Send + Sync {
+    /// 需要订阅的主题
+    fn subscriptions(&self) -> Vec<Subscription>;
+
+    /// 消息处理，仅在主题匹配订阅时调用
+    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()>;
+
+    /// 带 MQTT5 发布属性的消息处理，默认忽略属性
+    async fn proc_with_properties(
+        &self,
+        topic: String,
+        payload: Bytes,
+        _properties: Option<PublishProperties>,
+    ) -> anyhow::Result<()> {
+        self.proc(topic, payload).await
+    }
 }
 
-/// DefaultHandler, don't match any topic.
+/// 消息处理器注册表，按订阅主题分发消息
 #[derive(Clone, Default)]
-pub struct DefaultHandler;
+pub struct HandlerRegistry {
+    handlers: Vec<Arc<dyn Handler>>,
+}
+
+impl HandlerRegistry {
+    pub fn register(&mut self, handler: impl Handler + 'static) -> &mut Self {
+        for sub in handler.subscriptions() {
+            info!(
+                "Register handler for topic: {}, qos: {:?}",
+                sub.filter, sub.qos
+            );
+        }
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// 所有处理器订阅主题的并集，相同主题取最高服务质量
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        let mut subs: Vec<Subscription> = Vec::new();
+        for sub in self.handlers.iter().flat_map(|h| h.subscriptions()) {
+            match subs.iter_mut().find(|s| s.filter == sub.filter) {
+                Some(s) if sub.qos > s.qos => s.qos = sub.qos,
+                Some(_) => {}
+                None => subs.push(sub),
+            }
+        }
+        subs
+    }
+
+    /// 主题对应的错误回复主题
+    pub fn reply_topic(&self, topic: &str) -> Option<String> {
+        self.handlers
+            .iter()
+            .flat_map(|h| h.subscriptions())
+            .find(|sub| topic::matches(&sub.filter, topic).is_some())
+            .and_then(|sub| sub.reply)
+    }
+
+    /// 分发消息到首个订阅匹配的处理器
+    pub async fn dispatch(
+        &self,
+        topic: String,
+        payload: Bytes,
+        properties: Option<PublishProperties>,
+    ) -> anyhow::Result<()> {
+        for handler in self.handlers.iter() {
+            let matched = handler
+                .subscriptions()
+                .iter()
+                .any(|sub| topic::matches(&sub.filter, &topic).is_some());
+            if matched {
+                return handler
+                    .proc_with_properties(topic, payload, properties)
+                    .await;
+            }
+        }
 
-impl Handler for DefaultHandler {
-    async fn proc(&self, topic: String, _: Bytes) -> anyhow::Result<()> {
         anyhow::bail!("No handler matched for topic: {topic}")
     }
 }
@@ -33,3 +137,98 @@ pub use house_set::HouseSetHandler;
 
 mod alarm_confirm;
 pub use alarm_confirm::{AlarmConfirm, AlarmConfirmHandler};
+
+mod broadcast;
+pub use broadcast::BroadcastHandler;
+
+mod command;
+pub use command::{Command, CommandHandler, QueueDepths};
+
+#[cfg(test)]
+mod handler_tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use rumqttc::v5::mqttbytes::QoS;
+
+    use super::{Handler, HandlerRegistry, Subscription};
+
+    struct CountHandler {
+        filters: Vec<&'static str>,
+        reply: Option<&'static str>,
+        qos: QoS,
+        count: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Handler for CountHandler {
+        fn subscriptions(&self) -> Vec<Subscription> {
+            self.filters
+                .iter()
+                .map(|f| {
+                    let sub = Subscription::new(f.to_string()).with_qos(self.qos);
+                    match self.reply {
+                        Some(reply) => sub.with_reply(reply.to_string()),
+                        None => sub,
+                    }
+                })
+                .collect()
+        }
+
+        async fn proc(&self, _: String, _: Bytes) -> anyhow::Result<()> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let alarm = Arc::new(AtomicUsize::new(0));
+        let config = Arc::new(AtomicUsize::new(0));
+        let mut registry = HandlerRegistry::default();
+        registry
+            .register(CountHandler {
+                filters: vec!["$share/ap/+/+/alarm"],
+                reply: None,
+                qos: QoS::AtLeastOnce,
+                count: alarm.clone(),
+            })
+            .register(CountHandler {
+                filters: vec!["ap/alarm/farm_config", "$share/ap/+/+/alarm"],
+                reply: Some("ap/alarm/farm_config/result"),
+                qos: QoS::ExactlyOnce,
+                count: config.clone(),
+            });
+
+        let subs = registry.subscriptions();
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].qos, QoS::ExactlyOnce);
+
+        registry
+            .dispatch("h1/d1/alarm".to_string(), Bytes::new(), None)
+            .await
+            .unwrap();
+        registry
+            .dispatch("ap/alarm/farm_config".to_string(), Bytes::new(), None)
+            .await
+            .unwrap();
+        assert!(
+            registry
+                .dispatch("h1/d1/false_alarm".to_string(), Bytes::new(), None)
+                .await
+                .is_err()
+        );
+        assert_eq!(alarm.load(Ordering::SeqCst), 1);
+        assert_eq!(config.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            registry.reply_topic("ap/alarm/farm_config").as_deref(),
+            Some("ap/alarm/farm_config/result")
+        );
+        assert_eq!(registry.reply_topic("h1/d1/alarm"), None);
+    }
+}
@@ -1,68 +1,73 @@
+use async_trait::async_trait;
 use bytes::Bytes;
 use time::OffsetDateTime;
 use tokio::sync::mpsc::Sender;
 use tracing::info;
 
-use crate::{model::Alarm, task::Play};
+use crate::{
+    model::Alarm,
+    schema,
+    task::Play,
+    topic::{self, Topics},
+};
 
-use super::Handler;
+use super::{Handler, Subscription};
 
 #[derive(Clone)]
-pub struct ActAlarmHandler<H: Handler> {
-    topic: &'static str,
-    repub_topic: &'static str,
+pub struct ActAlarmHandler {
+    topic: String,
+    repub_topic: String,
     tx: Sender<Alarm>,
-    child_handler: Option<H>,
     play: Play,
 }
 
-impl<H: Handler> ActAlarmHandler<H> {
-    pub fn new(tx: Sender<Alarm>, play: Play) -> Self {
+impl ActAlarmHandler {
+    pub fn new(tx: Sender<Alarm>, play: Play, topics: &Topics) -> Self {
         Self {
-            topic: "alarm",
-            repub_topic: "repub_alarms",
+            topic: topics.shared(&topics.alarm),
+            repub_topic: topics.shared(&topics.repub_alarm),
             tx,
-            child_handler: None,
             play,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic) || topic.ends_with(self.repub_topic);
+    /// 匹配报警主题，返回主题中的鸡舍码
+    fn mat<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        let captures = topic::matches(&self.topic, topic)
+            .or_else(|| topic::matches(&self.repub_topic, topic))?;
+        // 主题末尾为 {house_code}/+/alarm
+        captures.len().checked_sub(2).map(|i| captures[i])
     }
 
     fn deserialize(&self, data: Bytes) -> anyhow::Result<Alarm> {
-        let payload = serde_json::from_slice::<Alarm>(&data)?;
+        let payload = schema::decode::<Alarm>(&data)?;
         Ok(payload)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for ActAlarmHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
+#[async_trait]
+impl Handler for ActAlarmHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![
+            Subscription::new(self.topic.clone()),
+            Subscription::new(self.repub_topic.clone()),
+        ]
+    }
 
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
+        let house_code = match self.mat(&topic) {
+            Some(house_code) => house_code.to_string(),
+            None => anyhow::bail!("Can't extract house code from topic: {topic}"),
+        };
 
         let mut alarm = self.deserialize(payload)?;
         alarm.received_time = Some(OffsetDateTime::now_utc());
-        if let Some(house_code) = topic.split("/").next() {
-            alarm.house_code = house_code.to_string();
-        }
+        alarm.house_code = house_code;
 
         info!("Received alarm: {:?}", alarm);
         self.tx.send(alarm).await.map_err(|e| anyhow::anyhow!(e))?;
 
-        self.play.cancel_test_play().await;
+        self.play.cancel_for_alarm().await;
 
         Ok(())
     }
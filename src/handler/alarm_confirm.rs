@@ -1,11 +1,13 @@
+use async_trait::async_trait;
 use bytes::Bytes;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-use crate::{Service, model::Alarm};
+use crate::{Service, model::Alarm, schema, topic::Topics};
 
-use super::Handler;
+use super::{Handler, Subscription};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AlarmConfirm {
     pub house_code: String,
@@ -14,47 +16,34 @@ pub struct AlarmConfirm {
 }
 
 #[derive(Clone)]
-pub struct AlarmConfirmHandler<H: Handler> {
-    topic: &'static str,
+pub struct AlarmConfirmHandler {
+    topic: String,
+    reply_topic: String,
     service: Service,
-    child_handler: Option<H>,
 }
 
-impl<H: Handler> AlarmConfirmHandler<H> {
-    pub fn new(service: Service) -> Self {
+impl AlarmConfirmHandler {
+    pub fn new(service: Service, topics: &Topics) -> Self {
         Self {
-            topic: "confirm",
+            topic: topics.alarm_confirm.clone(),
+            reply_topic: topics.alarm_confirm_result.clone(),
             service,
-            child_handler: None,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    pub fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic);
-    }
-
     fn deserialize(&self, data: Bytes) -> anyhow::Result<Vec<AlarmConfirm>> {
-        let payload = serde_json::from_slice::<Vec<AlarmConfirm>>(&data)?;
+        let payload = schema::decode::<Vec<AlarmConfirm>>(&data)?;
         Ok(payload)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for AlarmConfirmHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
-
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+#[async_trait]
+impl Handler for AlarmConfirmHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone()).with_reply(self.reply_topic.clone())]
+    }
 
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
         let confirms = self.deserialize(payload)?;
         let mut alarms = Vec::new();
         for c in confirms {
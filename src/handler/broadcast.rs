@@ -0,0 +1,81 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use tokio::sync::mpsc::Sender;
+use tracing::info;
+
+use crate::{Service, model::Broadcast, schema, task::Play, topic::Topics};
+
+use super::{Handler, Subscription};
+
+#[derive(Clone)]
+pub struct BroadcastHandler {
+    topic: String,
+    reply_topic: String,
+    tx: Sender<Broadcast>,
+    play: Play,
+    service: Service,
+}
+
+impl BroadcastHandler {
+    pub fn new(tx: Sender<Broadcast>, play: Play, service: Service, topics: &Topics) -> Self {
+        Self {
+            topic: topics.broadcast.clone(),
+            reply_topic: topics.broadcast_result.clone(),
+            tx,
+            play,
+            service,
+        }
+    }
+
+    fn deserialize(&self, data: Bytes) -> anyhow::Result<Broadcast> {
+        let payload = schema::decode::<Broadcast>(&data)?;
+        Ok(payload)
+    }
+
+    /// 校验目标鸡舍及音柱是否已配置
+    async fn check_targets(&self, broadcast: &Broadcast) -> anyhow::Result<()> {
+        let service = self.service.read().await;
+        for house in &broadcast.houses {
+            if !service.house_set.contains_key(house) {
+                anyhow::bail!("Unknown house: {house}");
+            }
+        }
+
+        if let Some(device_ids) = &broadcast.device_ids {
+            for id in device_ids {
+                if !service.soundposts.device_ids.contains(id) {
+                    anyhow::bail!("Unknown soundpost device: {id}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler for BroadcastHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone()).with_reply(self.reply_topic.clone())]
+    }
+
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
+        let mut broadcast = self.deserialize(payload)?;
+        broadcast.validate()?;
+        self.check_targets(&broadcast).await?;
+        if let Some(media_file) = &broadcast.media_file {
+            broadcast.media_file = Some(self.play.resolve_media_file(media_file)?);
+        }
+
+        info!("Received broadcast: {:?}", broadcast);
+        self.tx
+            .send(broadcast)
+            .await
+            .map_err(|e| anyhow::anyhow!(e))?;
+
+        // 广播优先于测试报警
+        self.play.cancel_test_play().await;
+
+        Ok(())
+    }
+}
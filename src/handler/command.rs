@@ -0,0 +1,367 @@
+use std::{sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::sync::mpsc::Sender;
+use tracing::error;
+
+use crate::{
+    Service,
+    model::Alarm,
+    mqtt_client::MqttClient,
+    schema,
+    service::SilenceScope,
+    task::{DbMonitor, HeartbeatData, Play},
+    topic::Topics,
+};
+
+use super::{Handler, Subscription};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Command {
+    pub command: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+/// stop 指令参数，均为空时停止当前任意播放
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StopArgs {
+    /// 仅当前播放属于该鸡舍时停止，并作为静音范围
+    house_code: Option<String>,
+    /// 仅当前播放为该报警时停止，并作为静音范围
+    alarm_key: Option<String>,
+    /// 静音时长，0 表示解除静音，最长 7 天
+    silence_minutes: Option<i64>,
+}
+
+/// 静音时长上限，单位 min
+const MAX_SILENCE_MINUTES: i64 = 7 * 24 * 60;
+
+/// 计算静音截止时间，0 表示解除静音
+fn silence_until(now: OffsetDateTime, minutes: i64) -> anyhow::Result<Option<OffsetDateTime>> {
+    if !(0..=MAX_SILENCE_MINUTES).contains(&minutes) {
+        anyhow::bail!(
+            "Invalid silenceMinutes: {minutes}, must be between 0 and {MAX_SILENCE_MINUTES}"
+        );
+    }
+    if minutes == 0 {
+        return Ok(None);
+    }
+    now.checked_add(time::Duration::minutes(minutes))
+        .map(Some)
+        .ok_or_else(|| anyhow::anyhow!("Invalid silenceMinutes: {minutes}"))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommandReply {
+    /// 0: 正常 1: 错误
+    pub code: i32,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActiveAlarm {
+    house_code: String,
+    target_name: String,
+    alarm_item: String,
+    content: String,
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    is_confirmed: bool,
+}
+
+impl From<&Alarm> for ActiveAlarm {
+    fn from(alarm: &Alarm) -> Self {
+        Self {
+            house_code: alarm.house_code.clone(),
+            target_name: alarm.target_name.clone(),
+            alarm_item: alarm.alarm_item.clone(),
+            content: alarm.content.clone(),
+            timestamp: alarm.timestamp,
+            is_confirmed: alarm.is_confirmed,
+        }
+    }
+}
+
+type DepthFn = Arc<dyn Fn() -> usize + Send + Sync>;
+
+/// 内部队列深度，仅持有弱引用，不影响队列关闭
+#[derive(Clone, Default)]
+pub struct QueueDepths {
+    queues: Vec<(String, DepthFn)>,
+}
+
+impl QueueDepths {
+    pub fn add<T: Send + 'static>(&mut self, name: &str, tx: &Sender<T>) -> &mut Self {
+        let tx = tx.downgrade();
+        let depth = move || match tx.upgrade() {
+            Some(tx) => tx.max_capacity() - tx.capacity(),
+            None => 0,
+        };
+        self.queues.push((name.to_string(), Arc::new(depth)));
+        self
+    }
+
+    pub fn depths(&self) -> serde_json::Map<String, Value> {
+        self.queues
+            .iter()
+            .map(|(name, depth)| (name.clone(), json!(depth())))
+            .collect()
+    }
+}
+
+/// 运行时查询及控制指令
+///
+/// 使用 MQTT5 响应主题及关联数据实现请求/响应，请求未指定响应主题时回复到 command_result
+#[derive(Clone)]
+pub struct CommandHandler {
+    topic: String,
+    result_topic: String,
+    service: Service,
+    play: Play,
+    client: MqttClient,
+    queues: QueueDepths,
+    db_monitor: DbMonitor,
+    // 脱敏后的配置
+    config: Value,
+    started: Instant,
+}
+
+impl CommandHandler {
+    pub fn new(
+        service: Service,
+        play: Play,
+        client: MqttClient,
+        queues: QueueDepths,
+        db_monitor: DbMonitor,
+        config: Value,
+        topics: &Topics,
+    ) -> Self {
+        Self {
+            topic: topics.command.clone(),
+            result_topic: topics.command_result.clone(),
+            service,
+            play,
+            client,
+            queues,
+            db_monitor,
+            config,
+            started: Instant::now(),
+        }
+    }
+
+    /// 停止当前播放，可选静音一段时间；silenceMinutes 为 0 时仅解除静音，不停止播放
+    async fn stop(&self, args: Value) -> anyhow::Result<Value> {
+        let args: StopArgs = if args.is_null() {
+            StopArgs::default()
+        } else {
+            serde_json::from_value(args)?
+        };
+
+        // 先校验参数，参数错误时不停止播放
+        let until = args
+            .silence_minutes
+            .map(|minutes| silence_until(OffsetDateTime::now_utc(), minutes))
+            .transpose()?;
+
+        let cancelled = if args.silence_minutes == Some(0) {
+            None
+        } else {
+            self.play
+                .stop(args.house_code.as_deref(), args.alarm_key.as_deref())
+                .await
+        };
+
+        let silenced = match until {
+            Some(until) => {
+                let scope = match (args.alarm_key, args.house_code) {
+                    (Some(key), _) => SilenceScope::Alarm(key),
+                    (None, Some(code)) => SilenceScope::House(code),
+                    (None, None) => SilenceScope::All,
+                };
+                {
+                    let mut service = self.service.write().await;
+                    service.set_silence(&scope, until);
+                }
+
+                let mut silenced = serde_json::to_value(&scope)?;
+                if let Value::Object(map) = &mut silenced {
+                    let until = match until {
+                        Some(t) => json!(t.format(&Rfc3339)?),
+                        None => Value::Null,
+                    };
+                    map.insert("until".to_string(), until);
+                }
+                silenced
+            }
+            None => Value::Null,
+        };
+
+        Ok(json!({
+            "cancelled": cancelled,
+            "silenced": silenced,
+        }))
+    }
+
+    /// 回复到请求的响应主题，携带关联数据
+    fn reply(
+        &self,
+        result: &anyhow::Result<Value>,
+        properties: Option<PublishProperties>,
+    ) -> anyhow::Result<()> {
+        let reply = match result {
+            Ok(data) => CommandReply {
+                code: 0,
+                message: "Success".to_string(),
+                data: Some(data.clone()),
+            },
+            Err(e) => CommandReply {
+                code: 1,
+                message: e.to_string(),
+                data: None,
+            },
+        };
+
+        let (response_topic, correlation_data) = match properties {
+            Some(p) => (p.response_topic, p.correlation_data),
+            None => (None, None),
+        };
+        let response_topic = response_topic.unwrap_or_else(|| self.result_topic.clone());
+        self.client.try_publish_response(
+            response_topic,
+            serde_json::to_string(&reply)?,
+            correlation_data,
+        )
+    }
+
+    async fn execute(&self, command: Command) -> anyhow::Result<Value> {
+        if command.command == "stop" {
+            return self.stop(command.args).await;
+        }
+
+        let service = self.service.read().await;
+        let data = match command.command.as_str() {
+            "list_active_alarms" => {
+                let alarms: Vec<ActiveAlarm> =
+                    service.alarm_set.values().map(ActiveAlarm::from).collect();
+                serde_json::to_value(alarms)?
+            }
+            "get_status" => {
+                let mut status = serde_json::to_value(HeartbeatData::new(
+                    &service,
+                    self.started.elapsed().as_secs(),
+                ))?;
+                if let Value::Object(map) = &mut status {
+                    map.insert("language".to_string(), json!(service.language));
+                    map.insert("crontab".to_string(), json!(service.crontab));
+                    map.insert("dbConnected".to_string(), json!(service.db.is_some()));
+                    map.insert("pendingRecords".to_string(), json!(service.records.len()));
+                    map.insert("pendingEvents".to_string(), json!(service.events.len()));
+                }
+                status
+            }
+            "get_queue_depths" => Value::Object(self.queues.depths()),
+            "get_config" => self.config.clone(),
+            "get_next_test_time" => match service.next_fire_time() {
+                Some(t) => json!(t.format(&Rfc3339)?),
+                None => Value::Null,
+            },
+            other => anyhow::bail!("Unknown command: {other}"),
+        };
+
+        Ok(data)
+    }
+}
+
+#[async_trait]
+impl Handler for CommandHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone())]
+    }
+
+    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
+        self.proc_with_properties(topic, payload, None).await
+    }
+
+    async fn proc_with_properties(
+        &self,
+        _: String,
+        payload: Bytes,
+        properties: Option<PublishProperties>,
+    ) -> anyhow::Result<()> {
+        let result = match schema::decode::<Command>(&payload) {
+            Ok(command) if command.command == "reload_config" => {
+                // 数据库加载耗时较长，不阻塞事件循环，完成后回复
+                let handler = self.clone();
+                tokio::spawn(async move {
+                    let result = handler
+                        .db_monitor
+                        .reload()
+                        .await
+                        .map(|changes| json!({ "changes": changes }));
+                    if let Err(e) = &result {
+                        error!("Reload db config failed: {e}");
+                    }
+                    if let Err(e) = handler.reply(&result, properties) {
+                        error!("Command reply failed: {e}");
+                    }
+                });
+                return Ok(());
+            }
+            Ok(command) => self.execute(command).await,
+            Err(e) => Err(e),
+        };
+        self.reply(&result, properties)?;
+
+        // 出错的指令同时转发到死信主题
+        result.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use time::OffsetDateTime;
+    use tokio::sync::mpsc::channel;
+
+    use super::{MAX_SILENCE_MINUTES, QueueDepths, silence_until};
+
+    #[test]
+    fn test_silence_until() {
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(silence_until(now, 0).unwrap(), None);
+        assert_eq!(
+            silence_until(now, 30).unwrap(),
+            Some(now + time::Duration::minutes(30))
+        );
+        assert!(silence_until(now, MAX_SILENCE_MINUTES).unwrap().is_some());
+        assert!(silence_until(now, MAX_SILENCE_MINUTES + 1).is_err());
+        assert!(silence_until(now, -1).is_err());
+        assert!(silence_until(now, i64::MAX).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_queue_depths() {
+        let (tx, mut rx) = channel::<u32>(10);
+        let mut queues = QueueDepths::default();
+        queues.add("act_alarm", &tx);
+
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        assert_eq!(queues.depths()["act_alarm"], 2);
+
+        rx.recv().await.unwrap();
+        assert_eq!(queues.depths()["act_alarm"], 1);
+
+        drop(tx);
+        assert_eq!(queues.depths()["act_alarm"], 0);
+    }
+}
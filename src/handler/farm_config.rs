@@ -1,11 +1,15 @@
+use async_trait::async_trait;
 use bytes::Bytes;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-use crate::{Service, service::BoxConfig, task::Play};
+use crate::{
+    Service, persist::ConfigUpdate, schema, service::BoxConfig, task::Play, topic::Topics,
+};
 
-use super::Handler;
+use super::{Handler, Subscription};
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FarmConfig {
     pub pause: Option<bool>,
@@ -14,50 +18,38 @@ pub struct FarmConfig {
 }
 
 #[derive(Clone)]
-pub struct FarmConfigHandler<H: Handler> {
-    topic: &'static str,
+pub struct FarmConfigHandler {
+    topic: String,
+    reply_topic: String,
     play: Play,
-    child_handler: Option<H>,
     service: Service,
 }
 
-impl<H: Handler> FarmConfigHandler<H> {
-    pub fn new(play: Play, service: Service) -> Self {
+impl FarmConfigHandler {
+    pub fn new(play: Play, service: Service, topics: &Topics) -> Self {
         Self {
-            topic: "farm_config",
+            topic: topics.farm_config.clone(),
+            reply_topic: topics.farm_config_result.clone(),
             play,
             service,
-            child_handler: None,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    pub fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic);
-    }
-
     fn deserialize(&self, data: Bytes) -> anyhow::Result<FarmConfig> {
-        let payload = serde_json::from_slice::<FarmConfig>(&data)?;
+        let payload = schema::decode::<FarmConfig>(&data)?;
         Ok(payload)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for FarmConfigHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
-
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+#[async_trait]
+impl Handler for FarmConfigHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone()).with_reply(self.reply_topic.clone())]
+    }
 
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
         let fc = self.deserialize(payload)?;
+        let changed = fc.pause.is_some() || fc.lang.is_some() || fc.enable_box.is_some();
         if let Some(pause) = fc.pause {
             {
                 let mut service = self.service.write().await;
@@ -86,6 +78,11 @@ impl<H: Handler> Handler for FarmConfigHandler<H> {
             }
         }
 
+        if changed {
+            let service = self.service.read().await;
+            service.persist_config(ConfigUpdate::Farm(service.farm_snapshot()));
+        }
+
         Ok(())
     }
 }
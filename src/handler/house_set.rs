@@ -1,53 +1,40 @@
-use crate::{Service, service::House};
+use crate::{Service, persist::ConfigUpdate, schema, service::House, topic::Topics};
+use async_trait::async_trait;
 use bytes::Bytes;
 
-use super::Handler;
+use super::{Handler, Subscription};
 
 #[derive(Clone)]
-pub struct HouseSetHandler<H: Handler> {
-    topic: &'static str,
+pub struct HouseSetHandler {
+    topic: String,
     service: Service,
-    child_handler: Option<H>,
 }
 
-impl<H: Handler> HouseSetHandler<H> {
-    pub fn new(service: Service) -> Self {
+impl HouseSetHandler {
+    pub fn new(service: Service, topics: &Topics) -> Self {
         Self {
-            topic: "houses",
+            topic: topics.houses.clone(),
             service,
-            child_handler: None,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    pub fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic);
-    }
-
     fn deserialize(&self, data: Bytes) -> anyhow::Result<Vec<House>> {
-        let payload = serde_json::from_slice::<Vec<House>>(&data)?;
+        let payload = schema::decode::<Vec<House>>(&data)?;
         Ok(payload)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for HouseSetHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
-
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+#[async_trait]
+impl Handler for HouseSetHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone())]
+    }
 
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
         let houses = self.deserialize(payload)?;
         let mut service = self.service.write().await;
-        service.set_houses(houses);
+        service.set_houses(houses.clone());
+        service.persist_config(ConfigUpdate::Houses(houses));
 
         Ok(())
     }
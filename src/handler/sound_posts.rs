@@ -1,10 +1,12 @@
-use crate::{Service, service::PostConfig};
+use crate::{Service, persist::ConfigUpdate, schema, service::PostConfig, topic::Topics};
+use async_trait::async_trait;
 use bytes::Bytes;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-use super::Handler;
+use super::{Handler, Subscription};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Soundposts {
     pub device_ids: Option<Vec<u32>>,
@@ -12,57 +14,44 @@ pub struct Soundposts {
 }
 
 #[derive(Clone)]
-pub struct SoundpostsHandler<H: Handler> {
-    topic: &'static str,
+pub struct SoundpostsHandler {
+    topic: String,
     service: Service,
-    child_handler: Option<H>,
 }
 
-impl<H: Handler> SoundpostsHandler<H> {
-    pub fn new(service: Service) -> Self {
+impl SoundpostsHandler {
+    pub fn new(service: Service, topics: &Topics) -> Self {
         Self {
-            topic: "sound_posts",
+            topic: topics.sound_posts.clone(),
             service,
-            child_handler: None,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    pub fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic);
-    }
-
     pub fn deserialize(&self, data: Bytes) -> anyhow::Result<Soundposts> {
-        let payload = serde_json::from_slice::<Soundposts>(&data)?;
+        let payload = schema::decode::<Soundposts>(&data)?;
         Ok(payload)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for SoundpostsHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
-
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+#[async_trait]
+impl Handler for SoundpostsHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone())]
+    }
 
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
         let sp = self.deserialize(payload)?;
         if let Some(device_ids) = sp.device_ids {
-            let mut service = self.service.write().await;
-            service.set_soundposts(PostConfig {
+            let posts = PostConfig {
                 device_ids,
                 speed: match sp.speed {
                     Some(speed) => speed,
                     None => 50,
                 },
-            });
+            };
+            let mut service = self.service.write().await;
+            service.set_soundposts(posts.clone());
+            service.persist_config(ConfigUpdate::Soundposts(posts));
         }
 
         Ok(())
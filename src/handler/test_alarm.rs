@@ -1,3 +1,4 @@
+use async_trait::async_trait;
 use bytes::Bytes;
 use std::time::Duration;
 use time::{OffsetDateTime, PrimitiveDateTime};
@@ -8,55 +9,56 @@ use tokio::{
 use tracing::{error, info};
 
 use crate::{
-    Service, TOPIC_RESULT_CRONTAB,
+    Service,
     model::{Alarm, TestAlarmConfig},
+    persist::ConfigUpdate,
+    schema,
+    service::TestAlarmSnapshot,
+    topic::Topics,
 };
 
-use super::Handler;
+use super::{Handler, Subscription};
 
 #[derive(Clone)]
-pub struct TestAlarmHandler<H: Handler> {
-    topic: &'static str,
+pub struct TestAlarmHandler {
+    topic: String,
+    reply_topic: String,
     tx: Sender<TestAlarmConfig>,
-    child_handler: Option<H>,
+    service: Service,
 }
 
-impl<H: Handler> TestAlarmHandler<H> {
-    pub fn new(tx: Sender<TestAlarmConfig>) -> Self {
+impl TestAlarmHandler {
+    pub fn new(tx: Sender<TestAlarmConfig>, service: Service, topics: &Topics) -> Self {
         Self {
-            topic: "crontab",
+            topic: topics.crontab.clone(),
+            reply_topic: topics.crontab_result.clone(),
             tx,
-            child_handler: None,
+            service,
         }
     }
 
-    pub fn handler(mut self, handler: H) -> Self {
-        self.child_handler = Some(handler);
-        self
-    }
-
-    fn mat(&self, topic: &str) -> bool {
-        return topic.ends_with(self.topic);
-    }
-
     fn deserialize(&self, data: Bytes) -> anyhow::Result<TestAlarmConfig> {
-        let config = serde_json::from_slice::<TestAlarmConfig>(&data)?;
+        let config = schema::decode::<TestAlarmConfig>(&data)?;
         Ok(config)
     }
 }
 
-#[allow(unreachable_code)]
-impl<H: Handler> Handler for TestAlarmHandler<H> {
-    async fn proc(&self, topic: String, payload: Bytes) -> anyhow::Result<()> {
-        if !self.mat(&topic) {
-            if let Some(child) = self.child_handler.clone() {
-                return child.proc(topic, payload).await;
-            }
-
-            return anyhow::bail!("No handler matched for topic: {topic}");
-        }
+#[async_trait]
+impl Handler for TestAlarmHandler {
+    fn subscriptions(&self) -> Vec<Subscription> {
+        vec![Subscription::new(self.topic.clone()).with_reply(self.reply_topic.clone())]
+    }
 
+    async fn proc(&self, _: String, payload: Bytes) -> anyhow::Result<()> {
         let payload = self.deserialize(payload)?;
+        // 立即播放不修改配置
+        if !payload.play_now {
+            let service = self.service.read().await;
+            service.persist_config(ConfigUpdate::TestAlarm(TestAlarmSnapshot {
+                duration: Some(payload.duration),
+                crontab: payload.crontab.clone(),
+            }));
+        }
         self.tx
             .send(payload)
             .await
@@ -103,7 +105,8 @@ impl TestAlarm {
                                 if is_ongoing_alarm_exist {
                                     {
                                         let mut service = self.service.write().await;
-                                        service.publish(TOPIC_RESULT_CRONTAB, result).await;
+                                        let topic = service.topics.crontab_result.clone();
+                                        service.publish(topic, result).await;
                                     }
                                     continue;
 
@@ -120,7 +123,8 @@ impl TestAlarm {
                                         let result = "{\"code\": 1, \"message\": \"播放太频繁，请稍候再试\", \"data\": {}}".to_string();
                                         {
                                             let mut service = self.service.write().await;
-                                            service.publish(TOPIC_RESULT_CRONTAB, result).await;
+                                            let topic = service.topics.crontab_result.clone();
+                                            service.publish(topic, result).await;
                                         }
                                         continue;
                                     }
@@ -1,17 +1,91 @@
 use std::sync::Arc;
 
-use alarm_player::{app, config::Args, service::AlarmService};
+use alarm_player::{
+    app,
+    config::{Args, Command},
+    persist::ConfigPersist,
+    preflight,
+    reload::LevelSetter,
+    schema,
+    service::AlarmService,
+};
 use clap::Parser;
 use tokio::sync::RwLock;
+use tracing::error;
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    let config = alarm_player::config::Config::new(args.config.as_str()).unwrap();
-    tracing_subscriber::fmt()
+    let config = match alarm_player::config::Config::new(args.config.as_str()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let subscriber = tracing_subscriber::fmt()
         .with_env_filter(config.tracing.level())
-        .init();
+        .with_filter_reloading();
+    let handle = subscriber.reload_handle();
+    subscriber.init();
+    let set_level: LevelSetter = Box::new(move |level| {
+        handle.reload(EnvFilter::try_new(level)?)?;
+        Ok(())
+    });
+
+    match args.command {
+        Some(Command::Records { house, from, to }) => {
+            if let Err(e) = app::records(config, house, from, to) {
+                eprintln!("Query records failed: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Schemas { out }) => {
+            match schema::write_schemas(&out) {
+                Ok(files) => files.iter().for_each(|f| println!("{f}")),
+                Err(e) => {
+                    eprintln!("Write schemas failed: {e}");
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some(Command::Config { json }) => {
+            if let Err(e) = app::print_config(&args.config, json) {
+                eprintln!("Print config failed: {e}");
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    let missing = config.missing_secrets();
+    if !missing.is_empty() && !args.check {
+        error!(
+            "Missing required secrets: {}, set them in config, *_file or env.",
+            missing.join(", ")
+        );
+        std::process::exit(1);
+    }
+
+    if args.check {
+        let report = preflight::run(&config, &args.localization).await;
+        report.print();
+        std::process::exit(if report.passed() { 0 } else { 1 });
+    }
+
+    if config.preflight.on_startup() {
+        let report = preflight::run(&config, &args.localization).await;
+        report.log();
+        if report.required_failed() {
+            error!("Preflight required checks failed, exit...");
+            std::process::exit(1);
+        }
+    }
 
     let dbconfig = config.database.clone();
     let mut alarm_service = AlarmService::new(
@@ -23,7 +97,28 @@ async fn main() {
         dbconfig,
     );
 
-    alarm_service.init(args.localization).await.unwrap();
+    match ConfigPersist::open(&config.persist) {
+        Ok(persist) => alarm_service.set_config_persist(persist),
+        Err(e) => {
+            error!("Runtime state open failed: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(e) = alarm_service.init(args.localization).await {
+        error!("Alarm service init failed: {e}");
+        std::process::exit(1);
+    }
 
-    app::run(Arc::new(RwLock::new(alarm_service)), config).await;
+    if let Err(e) = app::run(
+        Arc::new(RwLock::new(alarm_service)),
+        config,
+        args.config,
+        Some(set_level),
+    )
+    .await
+    {
+        error!("{e}, exit...");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,80 @@
+use sea_orm::{DatabaseConnection, DbErr};
+use sea_orm_migration::{MigrationTrait, MigratorTrait, SchemaManager};
+
+mod m20261018_000001_create_tables;
+mod m20261020_000001_create_alarm_event_history;
+
+/// 数据库迁移
+///
+/// 中心库由平台维护，单机 SQLite 库启动时自动执行迁移建表
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261018_000001_create_tables::Migration),
+            Box::new(m20261020_000001_create_alarm_event_history::Migration),
+        ]
+    }
+}
+
+/// 创建报警事件表，已存在时不做处理
+///
+/// 报警事件表由播放器写入，中心库不执行迁移时单独建表，不写入迁移记录
+pub async fn create_event_table(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let manager = SchemaManager::new(db);
+    m20261020_000001_create_alarm_event_history::Migration
+        .up(&manager)
+        .await
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod migration_tests {
+    use sea_orm::Database;
+    use sea_orm_migration::MigratorTrait;
+    use time::OffsetDateTime;
+
+    use crate::model::{alarm_play_record, sys_house};
+
+    use super::{Migrator, create_event_table};
+
+    #[tokio::test]
+    async fn test_create_event_table() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        create_event_table(&db).await.unwrap();
+        // 重复执行不报错，之后执行完整迁移不冲突
+        create_event_table(&db).await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_migrate() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        // 重复执行不报错
+        Migrator::up(&db, None).await.unwrap();
+
+        assert!(sys_house::find_all(&db).await.unwrap().is_empty());
+
+        let now = OffsetDateTime::now_utc();
+        let now = time::PrimitiveDateTime::new(now.date(), now.time());
+        let record = alarm_play_record::Model {
+            id: uuid::Uuid::new_v4(),
+            house_code: "H01".to_string(),
+            house_name: None,
+            receiver_name: "音箱报警".to_string(),
+            receiver_sign: String::new(),
+            alarm_time: now,
+            alarm_grade: "场舍端警报".to_string(),
+            sending_state: true,
+            alarm_send_to: "Box".to_string(),
+            source_message: "{}".to_string(),
+            error_message: None,
+            creation_time: now,
+            is_deleted: false,
+            alarm_client: 0,
+        };
+        alarm_play_record::insert(record, &db).await.unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use sea_orm::{EntityTrait, Schema};
+use sea_orm_migration::prelude::*;
+
+use crate::model::{
+    alarm_play_record, farm_config_info, sound_column_config, sys_house, test_alarm_config,
+    test_alarm_play_record,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn create_table<E: EntityTrait>(manager: &SchemaManager<'_>, entity: E) -> Result<(), DbErr> {
+    let schema = Schema::new(manager.get_database_backend());
+    manager
+        .create_table(
+            schema
+                .create_table_from_entity(entity)
+                .if_not_exists()
+                .to_owned(),
+        )
+        .await
+}
+
+async fn drop_table<E: EntityTrait>(manager: &SchemaManager<'_>, entity: E) -> Result<(), DbErr> {
+    manager
+        .drop_table(Table::drop().table(entity).if_exists().to_owned())
+        .await
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        create_table(manager, sys_house::Entity).await?;
+        create_table(manager, farm_config_info::Entity).await?;
+        create_table(manager, sound_column_config::Entity).await?;
+        create_table(manager, test_alarm_config::Entity).await?;
+        create_table(manager, alarm_play_record::Entity).await?;
+        create_table(manager, test_alarm_play_record::Entity).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(manager, test_alarm_play_record::Entity).await?;
+        drop_table(manager, alarm_play_record::Entity).await?;
+        drop_table(manager, test_alarm_config::Entity).await?;
+        drop_table(manager, sound_column_config::Entity).await?;
+        drop_table(manager, farm_config_info::Entity).await?;
+        drop_table(manager, sys_house::Entity).await
+    }
+}
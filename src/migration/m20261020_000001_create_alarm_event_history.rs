@@ -0,0 +1,46 @@
+use sea_orm::Schema;
+use sea_orm_migration::prelude::*;
+
+use crate::model::alarm_event_history;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let schema = Schema::new(manager.get_database_backend());
+        manager
+            .create_table(
+                schema
+                    .create_table_from_entity(alarm_event_history::Entity)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // 按鸡舍及时间查询报警事件
+        manager
+            .create_index(
+                Index::create()
+                    .name("IX_AlarmEventHistory_HouseCode_CreationTime")
+                    .table(alarm_event_history::Entity)
+                    .col(alarm_event_history::Column::HouseCode)
+                    .col(alarm_event_history::Column::CreationTime)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(alarm_event_history::Entity)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -1,7 +1,12 @@
 mod alarm;
 pub use alarm::Alarm;
 
+pub mod alarm_event_history;
 pub mod alarm_play_record;
+
+mod broadcast;
+pub use broadcast::{Broadcast, BroadcastPriority};
+
 pub mod farm_config_info;
 pub mod sound_column_config;
 pub mod sys_house;
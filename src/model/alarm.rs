@@ -1,8 +1,9 @@
 use crate::util::rfc3339_time;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use time::{OffsetDateTime, PrimitiveDateTime};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct Alarm {
     #[serde(skip)]
@@ -14,6 +15,7 @@ pub struct Alarm {
     pub alarm_item: String,
     pub content: String,
     #[serde(rename = "TimeStamp", with = "rfc3339_time")]
+    #[schemars(with = "String", extend("format" = "date-time"))]
     pub timestamp: OffsetDateTime,
     #[serde(skip)]
     #[serde(default)]
@@ -28,8 +30,11 @@ pub struct Alarm {
     pub is_confirmed: bool,
     pub day_age: Option<u32>,
     // 测试报警计划执行时间
+    // 序列化为 [年, 年内天数, 时, 分, 秒, 纳秒]
+    #[schemars(with = "Option<(i32, u16, u8, u8, u8, u32)>")]
     pub test_plan_time: Option<PrimitiveDateTime>,
     // 测试报警实际执行时间
+    #[schemars(with = "Option<(i32, u16, u8, u8, u8, u32)>")]
     pub test_time: Option<PrimitiveDateTime>,
     // 是否新报警， 默认false， 指定为true 时 不管是否
     // 收到过相同类型的报警都会认为是新报警
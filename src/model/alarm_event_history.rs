@@ -0,0 +1,77 @@
+use sea_orm::{
+    ActiveModelBehavior, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey, DeriveRelation,
+    EntityTrait, EnumIter, PrimaryKeyTrait, sea_query::OnConflict,
+};
+use serde::{Deserialize, Serialize};
+use time::PrimitiveDateTime;
+
+/// 报警事件类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlarmEventType {
+    /// 收到报警
+    Received,
+    /// 收到消警
+    CancelReceived,
+    /// 丢弃的报警消息
+    Rejected,
+    /// 进入播放队列
+    Queued,
+    /// 已确认
+    Confirmed,
+    /// 取消确认
+    Unconfirmed,
+    /// 播放时报警已取消
+    Canceled,
+    /// 暂停播放: 全局暂停、已确认或空舍
+    Paused,
+    /// 远程静音
+    Suppressed,
+}
+
+impl AlarmEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlarmEventType::Received => "received",
+            AlarmEventType::CancelReceived => "cancel_received",
+            AlarmEventType::Rejected => "rejected",
+            AlarmEventType::Queued => "queued",
+            AlarmEventType::Confirmed => "confirmed",
+            AlarmEventType::Unconfirmed => "unconfirmed",
+            AlarmEventType::Canceled => "canceled",
+            AlarmEventType::Paused => "paused",
+            AlarmEventType::Suppressed => "suppressed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "AlarmEventHistory", rename_all = "PascalCase")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: uuid::Uuid,
+    pub house_code: String,
+    pub target_name: String,
+    pub alarm_item: String,
+    // `AlarmEventType`
+    pub event_type: String,
+    // 事件原因，如暂停、丢弃的原因
+    pub reason: Option<String>,
+    // 报警消息时间
+    pub alarm_time: PrimitiveDateTime,
+    pub creation_time: PrimitiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// 插入记录，已存在时忽略，重试写入不会产生重复记录
+pub async fn insert(record: Model, db: &DatabaseConnection) -> anyhow::Result<()> {
+    let record: ActiveModel = record.into();
+    Entity::insert(record)
+        .on_conflict(OnConflict::column(Column::Id).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
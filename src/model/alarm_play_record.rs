@@ -1,13 +1,14 @@
 use sea_orm::{
-    ActiveModelBehavior, ActiveModelTrait, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey,
-    DeriveRelation, EnumIter, PrimaryKeyTrait,
+    ActiveModelBehavior, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey, DeriveRelation,
+    EntityTrait, EnumIter, PrimaryKeyTrait, sea_query::OnConflict,
 };
+use serde::{Deserialize, Serialize};
 use time::PrimitiveDateTime;
 
-#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "AlarmRecordStorage", rename_all = "PascalCase")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: uuid::Uuid,
     pub house_code: String,
     pub house_name: Option<String>,
@@ -36,8 +37,12 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// 插入记录，已存在时忽略，重试写入不会产生重复记录
 pub async fn insert(record: Model, db: &DatabaseConnection) -> anyhow::Result<()> {
     let record: ActiveModel = record.into();
-    record.insert(db).await?;
+    Entity::insert(record)
+        .on_conflict(OnConflict::column(Column::Id).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
     Ok(())
 }
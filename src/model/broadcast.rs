@@ -0,0 +1,137 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 广播优先级
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BroadcastPriority {
+    /// 让位于报警: 有未取消报警时不播放，新报警到达时中断
+    #[default]
+    Normal,
+    /// 紧急: 不受报警影响，播放完成后再播报警
+    Urgent,
+}
+
+/// 重复次数上限，紧急广播不被报警中断，上限更低
+const MAX_REPEAT: u32 = 10;
+const MAX_URGENT_REPEAT: u32 = 3;
+/// 总播放时长上限，单位 s
+const MAX_DURATION_SECS: u64 = 600;
+const MAX_URGENT_DURATION_SECS: u64 = 120;
+
+/// 临时广播通知
+///
+/// 文字及媒体地址由音柱播放，音箱仅能播放本地预录文件
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Broadcast {
+    /// 音柱 TTS 文字
+    pub text: Option<String>,
+    /// 音柱播放的媒体地址
+    pub media_url: Option<String>,
+    /// 音箱播放的本地预录文件，为广播媒体目录下的相对路径
+    pub media_file: Option<String>,
+    /// 关联的鸡舍码，用于播放记录；鸡舍与音柱无对应关系，指定鸡舍时需同时指定 deviceIds
+    #[serde(default)]
+    pub houses: Vec<String>,
+    /// 目标音柱，为空时播放到全部已配置音柱
+    pub device_ids: Option<Vec<u32>>,
+    /// 是否使用音箱播放
+    #[serde(default = "default_true")]
+    pub soundbox: bool,
+    /// 重复次数，不超过 10，紧急广播不超过 3
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// 总播放时长上限，单位 s，未指定时按最小播放时长及重复次数估算；不超过 600，紧急广播不超过 120
+    pub duration_secs: Option<u64>,
+    #[serde(default)]
+    pub priority: BroadcastPriority,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+impl Broadcast {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.text.is_some() && self.media_url.is_some() {
+            anyhow::bail!("Broadcast text and mediaUrl are exclusive");
+        }
+        if self.text.is_none() && self.media_url.is_none() && self.media_file.is_none() {
+            anyhow::bail!("Broadcast requires text, mediaUrl or mediaFile");
+        }
+        // 不能按鸡舍选择音柱，避免面向鸡舍的广播播放到全部音柱
+        if !self.houses.is_empty() && self.device_ids.is_none() {
+            anyhow::bail!("Broadcast with houses requires deviceIds");
+        }
+        if self.repeat == 0 {
+            anyhow::bail!("Broadcast repeat must be greater than 0");
+        }
+        let (max_repeat, max_duration_secs) = match self.priority {
+            BroadcastPriority::Normal => (MAX_REPEAT, MAX_DURATION_SECS),
+            BroadcastPriority::Urgent => (MAX_URGENT_REPEAT, MAX_URGENT_DURATION_SECS),
+        };
+        if self.repeat > max_repeat {
+            anyhow::bail!("Broadcast repeat must not exceed {max_repeat}");
+        }
+        if let Some(duration_secs) = self.duration_secs
+            && duration_secs > max_duration_secs
+        {
+            anyhow::bail!("Broadcast durationSecs must not exceed {max_duration_secs}");
+        }
+
+        Ok(())
+    }
+
+    /// 播放记录中展示的内容
+    pub fn content(&self) -> String {
+        self.text
+            .clone()
+            .or_else(|| self.media_url.clone())
+            .or_else(|| self.media_file.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod broadcast_tests {
+    use super::{Broadcast, BroadcastPriority};
+
+    #[test]
+    fn test_deserialize() {
+        let b: Broadcast = serde_json::from_str(
+            r#"{"text":"饲料车进场，请清空3号通道","houses":["H01"],"deviceIds":[1]}"#,
+        )
+        .unwrap();
+        assert!(b.validate().is_ok());
+        let b: Broadcast = serde_json::from_str(r#"{"text":"x","houses":["H01"]}"#).unwrap();
+        assert!(b.validate().is_err());
+        assert_eq!(b.repeat, 1);
+        assert!(b.soundbox);
+        assert_eq!(b.priority, BroadcastPriority::Normal);
+
+        let b: Broadcast =
+            serde_json::from_str(r#"{"text":"a","mediaUrl":"http://a/b.mp3","priority":"urgent"}"#)
+                .unwrap();
+        assert_eq!(b.priority, BroadcastPriority::Urgent);
+        assert!(b.validate().is_err());
+
+        let b: Broadcast = serde_json::from_str(r#"{"repeat":2}"#).unwrap();
+        assert!(b.validate().is_err());
+
+        // 紧急广播不被报警中断，时长及重复次数上限更低
+        let b: Broadcast =
+            serde_json::from_str(r#"{"text":"x","priority":"urgent","durationSecs":31536000}"#)
+                .unwrap();
+        assert!(b.validate().is_err());
+        let b: Broadcast = serde_json::from_str(r#"{"text":"x","repeat":5}"#).unwrap();
+        assert!(b.validate().is_ok());
+        let b: Broadcast =
+            serde_json::from_str(r#"{"text":"x","repeat":5,"priority":"urgent"}"#).unwrap();
+        assert!(b.validate().is_err());
+    }
+}
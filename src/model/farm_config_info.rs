@@ -1,13 +1,13 @@
 use sea_orm::{
-    ActiveModelBehavior, ColumnTrait, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey,
-    DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, DeriveEntityModel,
+    DerivePrimaryKey, DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter, Set,
 };
 use time::PrimitiveDateTime;
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "FarmConfigInfo", rename_all = "PascalCase")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: uuid::Uuid,
     // 音箱音量
     pub local_volume: Option<i32>,
@@ -35,3 +35,34 @@ pub async fn find_one(db: &DatabaseConnection) -> anyhow::Result<Option<Model>>
 
     Ok(result)
 }
+
+/// 保存鸡场配置，不存在时新建
+pub async fn save(
+    db: &DatabaseConnection,
+    paused: bool,
+    lang: Option<String>,
+    speaker_enabled: bool,
+    volume: u32,
+) -> anyhow::Result<()> {
+    let existing = find_one(db).await?;
+    let is_new = existing.is_none();
+    let mut model: ActiveModel = match existing {
+        Some(model) => model.into(),
+        None => ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            sound_column_start_time: Set(None),
+            is_deleted: Set(false),
+            ..Default::default()
+        },
+    };
+    model.sound_column_pause = Set(Some(paused as i32));
+    model.alarm_content_lang = Set(lang);
+    model.speaker_state = Set(Some(speaker_enabled as i32));
+    model.local_volume = Set(Some(volume as i32));
+    if is_new {
+        model.insert(db).await?;
+    } else {
+        model.update(db).await?;
+    }
+    Ok(())
+}
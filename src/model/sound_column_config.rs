@@ -1,12 +1,12 @@
 use sea_orm::{
-    ActiveModelBehavior, ColumnTrait, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey,
-    DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, DeriveEntityModel,
+    DerivePrimaryKey, DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter, Set,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "SoundColumnConfig", rename_all = "PascalCase")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: uuid::Uuid,
     pub device_id: i32,
     pub speed: i32,
@@ -28,3 +28,40 @@ pub async fn find_all(db: &DatabaseConnection) -> anyhow::Result<Vec<Model>> {
 
     Ok(result)
 }
+
+/// 保存启用的音柱，列表外的音柱置为停用，不存在的音柱新建
+pub async fn save(db: &DatabaseConnection, device_ids: &[u32], speed: u8) -> anyhow::Result<()> {
+    let models = Entity::find()
+        .filter(Column::IsDeleted.eq(false))
+        .all(db)
+        .await?;
+
+    let mut saved = Vec::new();
+    for model in models {
+        let enabled = device_ids.contains(&(model.device_id as u32));
+        saved.push(model.device_id as u32);
+        if model.enabled == enabled && (!enabled || model.speed == speed as i32) {
+            continue;
+        }
+        let mut model: ActiveModel = model.into();
+        model.enabled = Set(enabled);
+        if enabled {
+            model.speed = Set(speed as i32);
+        }
+        model.update(db).await?;
+    }
+
+    for device_id in device_ids.iter().filter(|id| !saved.contains(id)) {
+        ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            device_id: Set(*device_id as i32),
+            speed: Set(speed as i32),
+            enabled: Set(true),
+            is_deleted: Set(false),
+        }
+        .insert(db)
+        .await?;
+    }
+
+    Ok(())
+}
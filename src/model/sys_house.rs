@@ -1,12 +1,13 @@
 use sea_orm::{
-    ActiveModelBehavior, ColumnTrait, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey,
-    DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, DeriveEntityModel,
+    DerivePrimaryKey, DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter, Set,
+    sea_query::Expr,
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "SysHouse", rename_all = "PascalCase")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: uuid::Uuid,
     pub name: String,
     pub enabled: bool,
@@ -28,3 +29,50 @@ pub async fn find_all(db: &DatabaseConnection) -> anyhow::Result<Vec<Model>> {
         .await?;
     Ok(result)
 }
+
+/// 按鸡舍码保存鸡舍状态，不存在时新建
+pub async fn save(
+    db: &DatabaseConnection,
+    house_code: &str,
+    name: &str,
+    enabled: bool,
+    is_empty: bool,
+) -> anyhow::Result<()> {
+    let model = Entity::find()
+        .filter(Column::HouseCode.eq(house_code))
+        .filter(Column::IsDeleted.eq(false))
+        .one(db)
+        .await?;
+
+    let is_new = model.is_none();
+    let mut model: ActiveModel = match model {
+        Some(model) => model.into(),
+        None => ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            house_code: Set(house_code.to_string()),
+            is_deleted: Set(false),
+            ..Default::default()
+        },
+    };
+    model.name = Set(name.to_string());
+    model.enabled = Set(enabled);
+    model.is_empty = Set(is_empty);
+    if is_new {
+        model.insert(db).await?;
+    } else {
+        model.update(db).await?;
+    }
+    Ok(())
+}
+
+/// 停用不在列表中的鸡舍，与整体下发的鸡舍列表保持一致
+pub async fn disable_others(db: &DatabaseConnection, house_codes: &[String]) -> anyhow::Result<()> {
+    Entity::update_many()
+        .col_expr(Column::Enabled, Expr::value(false))
+        .filter(Column::Enabled.eq(true))
+        .filter(Column::IsDeleted.eq(false))
+        .filter(Column::HouseCode.is_not_in(house_codes.iter().cloned()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
@@ -1,10 +1,11 @@
+use schemars::JsonSchema;
 use sea_orm::{
-    ActiveModelBehavior, ColumnTrait, DatabaseConnection, DeriveEntityModel, DerivePrimaryKey,
-    DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, DatabaseConnection, DeriveEntityModel,
+    DerivePrimaryKey, DeriveRelation, EntityTrait, EnumIter, PrimaryKeyTrait, QueryFilter, Set,
 };
 use serde::Deserialize;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TestAlarmConfig {
     pub duration: u64,
@@ -15,7 +16,7 @@ pub struct TestAlarmConfig {
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "TestAlarmConfig", rename_all = "PascalCase")]
 pub struct Model {
-    #[sea_orm(primary_key)]
+    #[sea_orm(primary_key, auto_increment = false)]
     pub id: uuid::Uuid,
     /// 测试时长，单位 s
     pub duration: Option<i32>,
@@ -49,3 +50,33 @@ pub async fn find_one(db: &DatabaseConnection) -> anyhow::Result<Option<Model>>
 
     Ok(None)
 }
+
+/// 保存测试报警配置，不存在时新建
+pub async fn save(
+    db: &DatabaseConnection,
+    duration: Option<u64>,
+    cron: Option<String>,
+) -> anyhow::Result<()> {
+    let existing = find_one(db).await?;
+    let is_new = existing.is_none();
+    let mut model: ActiveModel = match existing {
+        Some(model) => model.into(),
+        None => ActiveModel {
+            id: Set(uuid::Uuid::new_v4()),
+            sup_types: Set(0x01),
+            enabled: Set(true),
+            is_deleted: Set(false),
+            ..Default::default()
+        },
+    };
+    if let Some(duration) = duration {
+        model.duration = Set(Some(duration as i32));
+    }
+    model.cron = Set(cron);
+    if is_new {
+        model.insert(db).await?;
+    } else {
+        model.update(db).await?;
+    }
+    Ok(())
+}
@@ -1,70 +1,450 @@
-use rumqttc::v5::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, mqttbytes::QoS};
+use bytes::Bytes;
+use rumqttc::{
+    Outgoing, TlsConfiguration, Transport,
+    tokio_rustls::rustls::{ClientConfig, RootCertStore},
+    v5::{
+        AsyncClient, Event, EventLoop, Incoming, MqttOptions,
+        mqttbytes::{
+            QoS,
+            v5::{LastWill, PubAckReason, PublishProperties},
+        },
+    },
+};
+use serde::Serialize;
 use std::{sync::Arc, time::Duration};
-use tokio::sync::Notify;
+use time::OffsetDateTime;
+use tokio::sync::{Notify, watch};
 use tracing::{error, info, warn};
 
-use crate::{config::MqttConfig, handler::Handler};
+use crate::{
+    config::{MqttConfig, MqttTransport},
+    handler::HandlerRegistry,
+    outbox::{Outbox, OutboxMessage},
+    rfc3339_time,
+    topic::Topics,
+};
+
+/// 死信消息，记录处理失败的原始消息
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub topic: String,
+    pub payload: String,
+    pub error: String,
+    #[serde(with = "rfc3339_time")]
+    pub timestamp: OffsetDateTime,
+}
+
+/// 指令类消息处理失败时的错误回复
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReply {
+    /// 0: 正常 1: 错误
+    pub code: i32,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+}
+
+const STATUS_ONLINE: &str = r#"{"status":"online"}"#;
+const STATUS_OFFLINE: &str = r#"{"status":"offline"}"#;
 
 #[derive(Clone)]
 pub struct MqttClient {
     client: AsyncClient,
+    // 在线状态主题
+    status_topic: String,
+    // 死信主题
+    dead_letter_topic: Option<String>,
+    // 持久化发件箱
+    outbox: Outbox,
+    // 发件箱独立连接的配置
+    outbox_options: MqttOptions,
+    // 连接状态，由事件循环维护
+    connected: Arc<watch::Sender<bool>>,
 }
 
 impl MqttClient {
-    pub fn new(config: MqttConfig) -> (Self, EventLoop) {
+    pub fn new(
+        config: MqttConfig,
+        topics: &Topics,
+        outbox: Outbox,
+    ) -> anyhow::Result<(Self, EventLoop)> {
         let mut options = MqttOptions::new(config.client_id(), config.broker(), config.port());
+        match config.username() {
+            Some(username) => {
+                options.set_credentials(username, config.password().unwrap_or_default());
+            }
+            None => warn!("MQTT username not set, connect anonymously"),
+        }
         options
-            .set_credentials(config.username(), config.password())
             .set_keep_alive(Duration::from_secs(config.keep_alive().into()))
             .set_clean_start(config.clean_session())
-            .set_manual_acks(true);
+            .set_manual_acks(true)
+            .set_last_will(LastWill::new(
+                topics.player_status.clone(),
+                STATUS_OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+                None,
+            ))
+            .set_transport(Self::transport(&config)?);
 
         let (client, eventloop) = AsyncClient::new(options, 10);
-        (Self { client }, eventloop)
+        let outbox_options = Self::options(&config, format!("{}-outbox", config.client_id()))?;
+        Ok((
+            Self {
+                client,
+                status_topic: topics.player_status.clone(),
+                dead_letter_topic: topics.dead_letter.clone(),
+                outbox,
+                outbox_options,
+                connected: Arc::new(watch::channel(false).0),
+            },
+            eventloop,
+        ))
+    }
+
+    fn transport(config: &MqttConfig) -> anyhow::Result<Transport> {
+        let transport = match config.transport() {
+            MqttTransport::Tcp => Transport::tcp(),
+            MqttTransport::Tls => Transport::tls_with_config(Self::tls_config(config)?),
+            #[cfg(feature = "websocket")]
+            MqttTransport::Ws => Transport::ws(),
+            #[cfg(feature = "websocket")]
+            MqttTransport::Wss => Transport::wss_with_config(Self::tls_config(config)?),
+            #[cfg(not(feature = "websocket"))]
+            transport => {
+                anyhow::bail!(
+                    "Mqtt transport: {transport:?} requires the `websocket` feature, rebuild with --features websocket."
+                )
+            }
+        };
+
+        Ok(transport)
+    }
+
+    /// 根据 CA、客户端证书及 ALPN 配置生成 TLS 配置
+    /// 根证书使用 ca_file 或系统根证书，客户端证书及 ALPN 均可选
+    fn tls_config(config: &MqttConfig) -> anyhow::Result<TlsConfiguration> {
+        let read = |path: String| -> anyhow::Result<Vec<u8>> {
+            std::fs::read(&path).map_err(|e| anyhow::anyhow!("Read tls file: {path} failed: {e}"))
+        };
+
+        let mut roots = RootCertStore::empty();
+        match config.ca_file() {
+            Some(ca_file) => {
+                let ca = read(ca_file.clone())?;
+                let certs = rustls_pemfile::certs(&mut ca.as_slice())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| anyhow::anyhow!("Parse ca_file: {ca_file} failed: {e}"))?;
+                roots.add_parsable_certificates(certs);
+            }
+            None => {
+                let certs = rustls_native_certs::load_native_certs()
+                    .map_err(|e| anyhow::anyhow!("Load native root certificates failed: {e}"))?;
+                roots.add_parsable_certificates(certs);
+            }
+        }
+        if roots.is_empty() {
+            anyhow::bail!("No valid root certificate found.");
+        }
+
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+        let mut tls_config = match (config.client_cert_file(), config.client_key_file()) {
+            (Some(cert_file), Some(key_file)) => {
+                let cert = read(cert_file.clone())?;
+                let certs = rustls_pemfile::certs(&mut cert.as_slice())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| {
+                        anyhow::anyhow!("Parse client_cert_file: {cert_file} failed: {e}")
+                    })?;
+                if certs.is_empty() {
+                    anyhow::bail!("No valid certificate in client_cert_file: {cert_file}");
+                }
+                let key = read(key_file.clone())?;
+                let key = rustls_pemfile::private_key(&mut key.as_slice())
+                    .map_err(|e| anyhow::anyhow!("Parse client_key_file: {key_file} failed: {e}"))?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No valid key in client_key_file: {key_file}")
+                    })?;
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .map_err(|e| anyhow::anyhow!("Invalid client certificate: {e}"))?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => anyhow::bail!("Both client_cert_file and client_key_file are required for mTLS."),
+        };
+
+        if let Some(alpn) = config.alpn() {
+            tls_config.alpn_protocols = alpn.into_iter().map(String::into_bytes).collect();
+        }
+
+        Ok(TlsConfiguration::Rustls(Arc::new(tls_config)))
+    }
+
+    /// 辅助连接的配置，不设遗嘱，不保留会话
+    fn options(config: &MqttConfig, client_id: String) -> anyhow::Result<MqttOptions> {
+        let mut options = MqttOptions::new(client_id, config.broker(), config.port());
+        if let Some(username) = config.username() {
+            options.set_credentials(username, config.password().unwrap_or_default());
+        }
+        options
+            .set_keep_alive(Duration::from_secs(config.keep_alive().into()))
+            .set_clean_start(true)
+            .set_transport(Self::transport(config)?);
+
+        Ok(options)
+    }
+
+    /// 以独立的客户端 ID 连接代理，收到 ConnAck 后断开，用于启动前检查
+    pub async fn check_broker(config: &MqttConfig, timeout: Duration) -> anyhow::Result<()> {
+        // 避免与运行中的播放器使用相同客户端 ID 而将其踢下线
+        let options = Self::options(config, format!("{}-check", config.client_id()))?;
+
+        let (client, mut eventloop) = AsyncClient::new(options, 1);
+        let connect = async {
+            loop {
+                match eventloop.poll().await? {
+                    Event::Incoming(Incoming::ConnAck(_)) => return anyhow::Ok(()),
+                    _ => continue,
+                }
+            }
+        };
+        match tokio::time::timeout(timeout, connect).await {
+            Ok(result) => result?,
+            Err(_) => anyhow::bail!("Connect timeout after {:?}", timeout),
+        }
+
+        let _ = client.try_disconnect();
+        let _ = tokio::time::timeout(Duration::from_secs(1), eventloop.poll()).await;
+        Ok(())
+    }
+
+    /// 经发件箱发布，断线期间的消息在重连后按顺序补发
+    pub async fn publish(&mut self, topic: String, payload: String) {
+        self.publish_with_retain(topic, payload, false).await;
+    }
+
+    /// 发布保留消息，新订阅者可立即获取最新值
+    pub async fn publish_retained(&mut self, topic: String, payload: String) {
+        self.publish_with_retain(topic, payload, true).await;
     }
 
-    pub async fn publish(&mut self, topic: &'static str, payload: String) {
+    async fn publish_with_retain(&mut self, topic: String, payload: String, retain: bool) {
+        if !self.outbox.push(topic.clone(), payload.clone(), retain) {
+            error!("Outbox full, drop publish {} to topic: {}", payload, topic);
+        }
+    }
+
+    /// 直接发布，不经发件箱，未连接时丢弃，用于心跳等时效性消息
+    pub fn publish_volatile(&self, topic: String, payload: String) {
+        if !*self.connected.borrow() {
+            return;
+        }
         if let Err(e) = self
             .client
-            .publish(topic, QoS::AtLeastOnce, false, payload.clone())
-            .await
+            .try_publish(topic.clone(), QoS::AtLeastOnce, false, payload)
         {
-            error!(
-                "Failed for publish {} to topic: {}, err: {e}",
-                payload, topic
-            );
+            warn!("Failed for publish to topic: {topic}, err: {e}");
+        }
+    }
+
+    /// 经独立连接按顺序发出发件箱中的消息，收到 PubAck 后才从发件箱移除
+    ///
+    /// 独立连接上仅有发件箱消息且每次只发出一条，PubAck 可按 pkid 与消息对应；
+    /// 断线时未确认的消息由事件循环在重连后重发，进程退出时保留在发件箱中，重启后再次发送
+    pub async fn flush_outbox(&self, shutdown: Arc<Notify>) {
+        // 提前注册，避免两次等待之间错过停止通知
+        let stopped = shutdown.notified();
+        tokio::pin!(stopped);
+        stopped.as_mut().enable();
+
+        let (client, mut eventloop) = AsyncClient::new(self.outbox_options.clone(), 1);
+        // 已交给客户端、等待确认的消息及其 pkid
+        let mut sending: Option<OutboxMessage> = None;
+        let mut inflight: Option<u16> = None;
+        loop {
+            if sending.is_none()
+                && let Some(message) = self.outbox.front()
+            {
+                match client.try_publish(
+                    message.topic.clone(),
+                    QoS::AtLeastOnce,
+                    message.retain,
+                    message.payload.clone(),
+                ) {
+                    Ok(_) => sending = Some(message),
+                    Err(e) => error!("Outbox publish failed: {e}"),
+                }
+            }
+
+            tokio::select! {
+                _ = &mut stopped => break,
+                _ = self.outbox.notified(), if sending.is_none() => {}
+                event = eventloop.poll() => match event {
+                    Ok(Event::Outgoing(Outgoing::Publish(pkid))) => inflight = Some(pkid),
+                    Ok(Event::Incoming(Incoming::PubAck(ack))) if inflight == Some(ack.pkid) => {
+                        if !matches!(
+                            ack.reason,
+                            PubAckReason::Success | PubAckReason::NoMatchingSubscribers
+                        ) {
+                            error!("Outbox message rejected: {:?}, {:?}", ack.reason, sending);
+                        }
+                        // 等待确认期间消息可能因发件箱满被丢弃，此时不移除其后的消息
+                        if sending.is_some() && self.outbox.front() == sending {
+                            self.outbox.pop_front();
+                        }
+                        sending = None;
+                        inflight = None;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(
+                            "Outbox connection error: {e}, {} messages pending, auto reconnect...",
+                            self.outbox.len()
+                        );
+                        tokio::select! {
+                            _ = &mut stopped => break,
+                            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Stop outbox flushing, {} messages pending.",
+            self.outbox.len()
+        );
+        let _ = client.try_disconnect();
+        let _ = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                    _ => continue,
+                }
+            }
+        })
+        .await;
+    }
+
+    /// 发布指令回复，携带请求的关联数据
+    ///
+    /// 在事件循环中调用，使用 try_publish 避免请求队列满时阻塞事件循环
+    pub fn try_publish_response(
+        &self,
+        topic: String,
+        payload: String,
+        correlation_data: Option<Bytes>,
+    ) -> anyhow::Result<()> {
+        let properties = PublishProperties {
+            correlation_data,
+            ..Default::default()
+        };
+        self.client.try_publish_with_properties(
+            topic,
+            QoS::AtLeastOnce,
+            false,
+            payload,
+            properties,
+        )?;
+
+        Ok(())
+    }
+
+    /// 转发处理失败的消息到死信主题
+    ///
+    /// 在事件循环中调用，使用 try_publish 避免请求队列满时阻塞事件循环
+    fn dead_letter(&self, topic: &str, payload: &[u8], err: &anyhow::Error) {
+        let Some(dead_letter_topic) = self.dead_letter_topic.clone() else {
+            return;
+        };
+
+        let letter = DeadLetter {
+            topic: topic.to_string(),
+            payload: String::from_utf8_lossy(payload).to_string(),
+            error: err.to_string(),
+            timestamp: OffsetDateTime::now_utc(),
+        };
+        match serde_json::to_string(&letter) {
+            Ok(data) => {
+                if let Err(e) =
+                    self.client
+                        .try_publish(dead_letter_topic, QoS::AtLeastOnce, false, data)
+                {
+                    error!("Failed for publish dead letter: {e}");
+                }
+            }
+            Err(e) => error!("DeadLetter serialize failed: {e}"),
+        }
+    }
+
+    fn reply_error(&self, reply_topic: String, err: &anyhow::Error) {
+        let reply = ErrorReply {
+            code: 1,
+            message: err.to_string(),
+            data: None,
+        };
+        match serde_json::to_string(&reply) {
+            Ok(data) => {
+                if let Err(e) = self
+                    .client
+                    .try_publish(reply_topic, QoS::AtLeastOnce, false, data)
+                {
+                    error!("Failed for publish error reply: {e}");
+                }
+            }
+            Err(e) => error!("ErrorReply serialize failed: {e}"),
         }
     }
 
-    pub async fn subscribe<H: Handler>(
+    pub async fn subscribe(
         &self,
         mut eventloop: EventLoop,
-        topics: Vec<String>,
-        handler: &H,
+        registry: &HandlerRegistry,
         shutdown: Arc<Notify>,
     ) -> anyhow::Result<()> {
         tokio::select! {
             _ = shutdown.notified() => {
                 info!("Cancel mqtt subscribtions, waitting for mqtt disconnected...");
-                match tokio::time::timeout(
-                    Duration::from_secs(2),
-                    self.client.disconnect()
-                ).await {
+                // 正常断开不会触发遗嘱消息，主动发布离线状态
+                // 请求队列可能已满且事件循环不再驱动，使用 try_ 接口避免退出阻塞
+                self.connected.send_replace(false);
+                if let Err(e) = self.client.try_publish(
+                    self.status_topic.clone(),
+                    QoS::AtLeastOnce,
+                    true,
+                    STATUS_OFFLINE,
+                ) {
+                    error!("Failed for publish offline status: {e}");
+                }
+                if let Err(e) = self.client.try_disconnect() {
+                    error!("Mqtt disconnect failed: {e}");
+                }
+
+                // 继续驱动事件循环，将离线状态及断开请求发送出去
+                match tokio::time::timeout(Duration::from_secs(2), async {
+                    loop {
+                        match eventloop.poll().await {
+                            Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                            _ => continue,
+                        }
+                    }
+                })
+                .await
+                {
                     Ok(_) => info!("Mqtt disconnected."),
                     Err(_) => warn!("Force mqtt to disconneted.")
                 }
 
                 Ok(())
             }
-            result = self.consume(&mut eventloop, topics, handler) => result
+            result = self.consume(&mut eventloop, registry) => result
         }
     }
 
-    async fn consume<H: Handler>(
+    async fn consume(
         &self,
         eventloop: &mut EventLoop,
-        topics: Vec<String>,
-        handler: &H,
+        registry: &HandlerRegistry,
     ) -> anyhow::Result<()> {
         loop {
             match eventloop.poll().await {
@@ -72,33 +452,117 @@ impl MqttClient {
                     Event::Incoming(Incoming::Publish(packet)) => {
                         match std::str::from_utf8(&packet.topic) {
                             Ok(topic) => {
-                                if let Err(e) = self.client.ack(&packet).await {
-                                    error!("Ack failed: {e}");
+                                // 请求队列可能被发件箱占满，避免阻塞事件循环
+                                if self.client.try_ack(&packet).is_err() {
+                                    let client = self.client.clone();
+                                    let packet = packet.clone();
+                                    tokio::spawn(async move {
+                                        if let Err(e) = client.ack(&packet).await {
+                                            error!("Ack failed: {e}");
+                                        }
+                                    });
                                 }
-                                if let Err(e) = handler
-                                    .proc(topic.to_string(), packet.payload.clone())
+                                if let Err(e) = registry
+                                    .dispatch(
+                                        topic.to_string(),
+                                        packet.payload.clone(),
+                                        packet.properties.clone(),
+                                    )
                                     .await
                                 {
                                     error!("Payload proc failed: {e}");
+                                    self.dead_letter(topic, &packet.payload, &e);
+                                    if let Some(reply) = registry.reply_topic(topic) {
+                                        self.reply_error(reply, &e);
+                                    }
                                 }
                             }
                             Err(e) => error!("Topic extract failed: {e}"),
                         }
                     }
                     Event::Incoming(Incoming::ConnAck(_)) => {
-                        info!("MQTT connected, subscribe to broker...");
-                        for topic in &topics {
-                            self.client
-                                .subscribe(topic.to_string(), QoS::AtLeastOnce)
-                                .await?;
-                        }
+                        info!("MQTT connected, publish online status...");
+                        // 在独立任务中发送，请求队列满时不阻塞事件循环
+                        let client = self.client.clone();
+                        let status_topic = self.status_topic.clone();
+                        let subscriptions = registry.subscriptions();
+                        let connected = self.connected.clone();
+                        tokio::spawn(async move {
+                            let result = async {
+                                client
+                                    .publish(status_topic, QoS::AtLeastOnce, true, STATUS_ONLINE)
+                                    .await?;
+
+                                info!("Subscribe to broker...");
+                                for sub in subscriptions {
+                                    info!("Subscribe topic: {}, qos: {:?}", sub.filter, sub.qos);
+                                    client.subscribe(sub.filter, sub.qos).await?;
+                                }
+                                anyhow::Ok(())
+                            }
+                            .await;
+                            match result {
+                                // 订阅完成后再发布心跳等即时消息
+                                Ok(_) => {
+                                    connected.send_replace(true);
+                                }
+                                Err(e) => error!("Mqtt subscribe failed: {e}"),
+                            }
+                        });
                     }
                     _ => continue,
                 },
                 Err(e) => {
+                    self.connected.send_replace(false);
                     error!("MQTT error: {e}, auto reconnect...");
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod mqtt_client_tests {
+    use crate::{
+        config::{MqttConfig, OutboxConfig},
+        outbox::Outbox,
+        topic::Topics,
+    };
+
+    use super::MqttClient;
+
+    fn outbox() -> Outbox {
+        Outbox::open(&toml::from_str::<OutboxConfig>("path = \"\"").unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tls_config() {
+        let topics = Topics::default();
+        let config: MqttConfig = toml::from_str("transport = \"tls\"").unwrap();
+        assert!(MqttClient::new(config, &topics, outbox()).is_ok());
+
+        let config: MqttConfig =
+            toml::from_str("transport = \"tls\"\nca_file = \"/not/exists/ca.pem\"").unwrap();
+        assert!(MqttClient::new(config, &topics, outbox()).is_err());
+
+        let config: MqttConfig =
+            toml::from_str("transport = \"tls\"\nclient_cert_file = \"client.pem\"").unwrap();
+        assert!(MqttClient::new(config, &topics, outbox()).is_err());
+
+        // 公共 CA 下的双向认证，客户端证书文件不存在时返回错误
+        let config: MqttConfig = toml::from_str(
+            "transport = \"tls\"\nclient_cert_file = \"/not/exists/client.pem\"\n\
+             client_key_file = \"/not/exists/client.key\"\nalpn = [\"mqtt\"]",
+        )
+        .unwrap();
+        let err = MqttClient::new(config, &topics, outbox()).err().unwrap();
+        assert!(err.to_string().contains("client.pem"));
+    }
+
+    #[cfg(not(feature = "websocket"))]
+    #[tokio::test]
+    async fn test_websocket_disabled() {
+        let config: MqttConfig = toml::from_str("transport = \"wss\"").unwrap();
+        assert!(MqttClient::new(config, &Topics::default(), outbox()).is_err());
+    }
+}
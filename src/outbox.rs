@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{config::OutboxConfig, persistent_queue::PersistentQueue, rfc3339_time};
+
+/// 待发布消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxMessage {
+    pub topic: String,
+    pub payload: String,
+    pub retain: bool,
+    #[serde(with = "rfc3339_time")]
+    pub timestamp: OffsetDateTime,
+}
+
+/// MQTT 发件箱
+///
+/// 发布消息先写入发件箱文件，经独立连接按顺序发出，收到 PubAck 后才从文件中移除，
+/// 进程重启后继续发送未确认的消息；重启前已发出但未确认的消息可能重复发送，为至少一次
+#[derive(Clone)]
+pub struct Outbox {
+    queue: PersistentQueue<OutboxMessage>,
+}
+
+impl Outbox {
+    /// 打开发件箱，加载上次未发出的消息
+    pub fn open(config: &OutboxConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            queue: PersistentQueue::open(
+                "outbox",
+                config.path(),
+                config.max_messages(),
+                config.drop_policy(),
+            )?,
+        })
+    }
+
+    /// 加入待发布消息，返回消息是否入队
+    pub fn push(&self, topic: String, payload: String, retain: bool) -> bool {
+        self.queue.push(OutboxMessage {
+            topic,
+            payload,
+            retain,
+            timestamp: OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// 最早的待发布消息
+    pub fn front(&self) -> Option<OutboxMessage> {
+        self.queue.front()
+    }
+
+    /// 移除已确认的消息
+    pub fn pop_front(&self) {
+        self.queue.pop_front();
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// 因队列满丢弃的消息数
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// 等待新消息入队
+    pub async fn notified(&self) {
+        self.queue.notified().await;
+    }
+
+    /// 等待发件箱文件写入完成
+    pub async fn sync(&self) {
+        self.queue.sync().await;
+    }
+}
+
+#[cfg(test)]
+mod outbox_tests {
+    use crate::config::OutboxConfig;
+
+    use super::Outbox;
+
+    fn config(path: &str, max_messages: usize, drop_policy: &str) -> OutboxConfig {
+        toml::from_str(&format!(
+            "path = \"{path}\"\nmax_messages = {max_messages}\ndrop_policy = \"{drop_policy}\""
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_persist_and_reload() {
+        let path = format!("{}/outbox_reload.jsonl", std::env::temp_dir().display());
+        let _ = std::fs::remove_file(&path);
+
+        let outbox = Outbox::open(&config(&path, 10, "drop_oldest")).unwrap();
+        outbox.push("a".to_string(), "1".to_string(), false);
+        outbox.push("b".to_string(), "2".to_string(), true);
+        outbox.push("c".to_string(), "3".to_string(), false);
+        outbox.pop_front();
+        outbox.sync().await;
+
+        let outbox = Outbox::open(&config(&path, 10, "drop_oldest")).unwrap();
+        assert_eq!(outbox.len(), 2);
+        let front = outbox.front().unwrap();
+        assert_eq!(front.topic, "b");
+        assert!(front.retain);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_drop_policy() {
+        let outbox = Outbox::open(&config("", 2, "drop_oldest")).unwrap();
+        for topic in ["a", "b", "c"] {
+            assert!(outbox.push(topic.to_string(), String::new(), false));
+        }
+        assert_eq!(outbox.front().unwrap().topic, "b");
+        assert_eq!(outbox.dropped(), 1);
+
+        let outbox = Outbox::open(&config("", 2, "drop_newest")).unwrap();
+        for topic in ["a", "b"] {
+            assert!(outbox.push(topic.to_string(), String::new(), false));
+        }
+        assert!(!outbox.push("c".to_string(), String::new(), false));
+        assert_eq!(outbox.front().unwrap().topic, "a");
+        assert_eq!(outbox.len(), 2);
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+use crate::{
+    Service,
+    config::{PersistConfig, PersistMode},
+    model::{farm_config_info, sound_column_config, sys_house, test_alarm_config},
+    service::{DbSnapshot, FarmSnapshot, House, PostConfig, TestAlarmSnapshot},
+};
+
+/// 数据库模式下配置写入失败的重试间隔
+const RETRY_MIN: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// MQTT 下发的配置变更
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "config", rename_all = "snake_case")]
+pub enum ConfigUpdate {
+    Farm(FarmSnapshot),
+    Soundposts(PostConfig),
+    Houses(Vec<House>),
+    TestAlarm(TestAlarmSnapshot),
+}
+
+impl ConfigUpdate {
+    /// 写入数据库对应的配置表
+    pub async fn save(self, db: &DatabaseConnection) -> anyhow::Result<()> {
+        match self {
+            ConfigUpdate::Farm(farm) => {
+                farm_config_info::save(
+                    db,
+                    farm.paused,
+                    farm.language,
+                    farm.soundbox.enabled,
+                    farm.soundbox.volume,
+                )
+                .await
+            }
+            ConfigUpdate::Soundposts(posts) => {
+                sound_column_config::save(db, &posts.device_ids, posts.speed).await
+            }
+            // 下发的是完整鸡舍列表，未下发的鸡舍停用
+            ConfigUpdate::Houses(houses) => {
+                let codes: Vec<String> = houses.iter().map(|h| h.code.clone()).collect();
+                for house in houses {
+                    sys_house::save(
+                        db,
+                        &house.code,
+                        &house.name,
+                        house.enabled,
+                        house.is_empty_mode,
+                    )
+                    .await?;
+                }
+                sys_house::disable_others(db, &codes).await
+            }
+            ConfigUpdate::TestAlarm(test) => {
+                test_alarm_config::save(db, test.duration, test.crontab).await
+            }
+        }
+    }
+}
+
+/// 本地状态文件，记录 MQTT 下发过的配置
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RuntimeState {
+    pub farm: Option<FarmSnapshot>,
+    pub soundposts: Option<PostConfig>,
+    pub houses: Option<Vec<House>>,
+    pub test_alarm: Option<TestAlarmSnapshot>,
+}
+
+impl RuntimeState {
+    fn update(&mut self, update: ConfigUpdate) {
+        match update {
+            ConfigUpdate::Farm(farm) => self.farm = Some(farm),
+            ConfigUpdate::Soundposts(posts) => self.soundposts = Some(posts),
+            ConfigUpdate::Houses(houses) => self.houses = Some(houses),
+            ConfigUpdate::TestAlarm(test) => self.test_alarm = Some(test),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.updates().is_empty()
+    }
+
+    fn updates(&self) -> Vec<ConfigUpdate> {
+        let mut updates = Vec::new();
+        if let Some(farm) = &self.farm {
+            updates.push(ConfigUpdate::Farm(farm.clone()));
+        }
+        if let Some(posts) = &self.soundposts {
+            updates.push(ConfigUpdate::Soundposts(posts.clone()));
+        }
+        if let Some(houses) = &self.houses {
+            updates.push(ConfigUpdate::Houses(houses.clone()));
+        }
+        if let Some(test) = &self.test_alarm {
+            updates.push(ConfigUpdate::TestAlarm(test.clone()));
+        }
+        updates
+    }
+
+    /// 移除已写入数据库的配置，写入期间又有新的下发时保留
+    fn remove_saved(&mut self, saved: &ConfigUpdate) {
+        let kind = std::mem::discriminant(saved);
+        let unchanged = self
+            .updates()
+            .iter()
+            .find(|u| std::mem::discriminant(*u) == kind)
+            .is_some_and(|u| serde_json::to_value(u).ok() == serde_json::to_value(saved).ok());
+        if !unchanged {
+            return;
+        }
+        match saved {
+            ConfigUpdate::Farm(_) => self.farm = None,
+            ConfigUpdate::Soundposts(_) => self.soundposts = None,
+            ConfigUpdate::Houses(_) => self.houses = None,
+            ConfigUpdate::TestAlarm(_) => self.test_alarm = None,
+        }
+    }
+
+    /// 以下发过的配置覆盖数据库配置
+    fn overlay(&self, snapshot: &mut DbSnapshot) {
+        if let Some(farm) = &self.farm {
+            snapshot.farm = Some(farm.clone());
+        }
+        if let Some(posts) = &self.soundposts {
+            snapshot.soundposts = posts.clone();
+        }
+        if let Some(houses) = &self.houses {
+            snapshot.houses = houses.clone();
+        }
+        if let Some(test) = &self.test_alarm {
+            snapshot.test_alarm = Some(test.clone());
+        }
+    }
+}
+
+/// MQTT 下发配置的持久化
+///
+/// database 模式下每类配置只保留最新一次下发，写入本地状态文件后由后台任务写入数据库，
+/// 写入成功后移除；数据库长时间不可用时也不会丢弃
+#[derive(Clone, Default)]
+pub struct ConfigPersist {
+    mode: PersistMode,
+    state_path: String,
+    // file 模式为下发过的全部配置，database 模式为尚未写入数据库的配置
+    state: Arc<Mutex<RuntimeState>>,
+    notify: Arc<Notify>,
+    // 数据库重新连接的通知，结束退避等待
+    connected: Arc<Notify>,
+}
+
+impl ConfigPersist {
+    /// file 模式加载本地状态文件，database 模式加载上次未写入数据库的配置
+    pub fn open(config: &PersistConfig) -> anyhow::Result<Self> {
+        let mode = config.mode();
+        let state_path = config.state_path();
+        let state = match mode {
+            PersistMode::File | PersistMode::Database => match fs::read_to_string(&state_path) {
+                Ok(content) => {
+                    info!("Loaded runtime state from {state_path}");
+                    serde_json::from_str(&content)?
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => RuntimeState::default(),
+                Err(e) => return Err(e.into()),
+            },
+            _ => RuntimeState::default(),
+        };
+
+        Ok(Self {
+            mode,
+            state_path,
+            state: Arc::new(Mutex::new(state)),
+            notify: Arc::new(Notify::new()),
+            connected: Arc::new(Notify::new()),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RuntimeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// MQTT 下发的配置是否持久化
+    pub fn enabled(&self) -> bool {
+        self.mode != PersistMode::None
+    }
+
+    /// file 模式下以本地状态覆盖数据库加载的配置
+    pub fn overlay(&self, snapshot: &mut DbSnapshot) {
+        if self.mode == PersistMode::File {
+            self.lock().overlay(snapshot);
+        }
+    }
+
+    /// database 模式下是否有尚未写入数据库的配置
+    pub fn has_pending(&self) -> bool {
+        self.mode == PersistMode::Database && !self.lock().is_empty()
+    }
+
+    /// 保存配置变更，database 模式下先写入本地状态文件，由后台任务写入数据库
+    pub fn save(&self, update: ConfigUpdate) {
+        if self.mode == PersistMode::None {
+            return;
+        }
+        let mut state = self.lock();
+        state.update(update);
+        self.write(&state);
+        if self.mode == PersistMode::Database {
+            self.notify.notify_one();
+        }
+    }
+
+    /// 数据库已连接，立即重试写入
+    pub fn db_connected(&self) {
+        self.connected.notify_one();
+    }
+
+    fn write(&self, state: &RuntimeState) {
+        let write = || -> anyhow::Result<()> {
+            let tmp = format!("{}.tmp", self.state_path);
+            fs::write(&tmp, serde_json::to_string_pretty(state)?)?;
+            fs::rename(&tmp, &self.state_path)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            error!("Write runtime state: {} failed: {e}", self.state_path);
+        }
+    }
+
+    /// 将尚未写入的配置写入数据库，写入成功的配置从本地状态中移除
+    pub async fn write_pending(&self, db: &DatabaseConnection) -> anyhow::Result<()> {
+        let updates = self.lock().updates();
+        for update in updates {
+            update.clone().save(db).await?;
+            let mut state = self.lock();
+            state.remove_saved(&update);
+            self.write(&state);
+        }
+        Ok(())
+    }
+
+    /// database 模式下按序将下发的配置写入数据库，数据库未连接或写入失败时退避重试
+    ///
+    /// 被数据库拒绝的配置同样保留重试，直到同类配置再次下发
+    pub async fn flush(&self, service: Service, shutdown: Arc<Notify>) {
+        if self.mode != PersistMode::Database {
+            return;
+        }
+        // 提前注册，避免写入数据库期间错过停止通知
+        let stopped = shutdown.notified();
+        tokio::pin!(stopped);
+        stopped.as_mut().enable();
+
+        let mut retry = RETRY_MIN;
+        loop {
+            if !self.has_pending() {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = self.notify.notified() => {}
+                }
+                continue;
+            }
+
+            let db = service.read().await.db.clone();
+            let result = match db {
+                Some(db) => tokio::select! {
+                    _ = &mut stopped => break,
+                    result = self.write_pending(&db) => result,
+                },
+                None => Err(anyhow::anyhow!("Database is not connected")),
+            };
+
+            match result {
+                Ok(_) => retry = RETRY_MIN,
+                Err(e) => {
+                    warn!("Write MQTT config to db failed: {e}, retry in {retry:?}");
+                    tokio::select! {
+                        _ = &mut stopped => break,
+                        _ = tokio::time::sleep(retry) => retry = (retry * 2).min(RETRY_MAX),
+                        _ = self.connected.notified() => retry = RETRY_MIN,
+                    }
+                }
+            }
+        }
+
+        if self.has_pending() {
+            info!(
+                "Stop config flushing, pending config kept in {}",
+                self.state_path
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod persist_tests {
+    use crate::{
+        config::PersistConfig,
+        service::{DbSnapshot, PostConfig},
+    };
+
+    use super::{ConfigPersist, ConfigUpdate};
+
+    #[test]
+    fn test_file_overlay() {
+        let path = format!("{}/state_test.json", std::env::temp_dir().display());
+        let _ = std::fs::remove_file(&path);
+        let config: PersistConfig =
+            toml::from_str(&format!("mode = \"file\"\nstate_path = \"{path}\"")).unwrap();
+
+        let persist = ConfigPersist::open(&config).unwrap();
+        let posts = PostConfig {
+            device_ids: vec![3],
+            speed: 70,
+        };
+        persist.save(ConfigUpdate::Soundposts(posts.clone()));
+
+        // 重启后覆盖数据库配置，未下发的部分保持数据库配置
+        let persist = ConfigPersist::open(&config).unwrap();
+        let mut snapshot = DbSnapshot {
+            soundposts: PostConfig {
+                device_ids: vec![1, 2],
+                speed: 50,
+            },
+            ..Default::default()
+        };
+        persist.overlay(&mut snapshot);
+        assert_eq!(snapshot.soundposts, posts);
+        assert!(snapshot.farm.is_none());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_database_pending() {
+        let path = format!("{}/pending_state_test.json", std::env::temp_dir().display());
+        let _ = std::fs::remove_file(&path);
+        let config: PersistConfig =
+            toml::from_str(&format!("mode = \"database\"\nstate_path = \"{path}\"")).unwrap();
+
+        let persist = ConfigPersist::open(&config).unwrap();
+        assert!(!persist.has_pending());
+        for speed in [50, 70] {
+            persist.save(ConfigUpdate::Soundposts(PostConfig {
+                device_ids: vec![3],
+                speed,
+            }));
+        }
+
+        // 未写入数据库的配置重启后保留，同类配置只保留最新一次
+        let persist = ConfigPersist::open(&config).unwrap();
+        assert!(persist.has_pending());
+        let updates = persist.lock().updates();
+        assert_eq!(updates.len(), 1);
+        assert!(matches!(&updates[0], ConfigUpdate::Soundposts(p) if p.speed == 70));
+
+        // 写入期间再次下发的配置不移除
+        persist
+            .lock()
+            .remove_saved(&ConfigUpdate::Soundposts(PostConfig {
+                device_ids: vec![3],
+                speed: 50,
+            }));
+        assert!(persist.has_pending());
+        persist.lock().remove_saved(&updates[0]);
+        assert!(!persist.has_pending());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_database_save() {
+        use sea_orm::Database;
+        use sea_orm_migration::MigratorTrait;
+
+        use crate::{
+            migration::Migrator,
+            service::{BoxConfig, FarmSnapshot, House, TestAlarmSnapshot},
+        };
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        let farm = FarmSnapshot {
+            paused: true,
+            language: Some("en".to_string()),
+            soundbox: BoxConfig {
+                enabled: true,
+                volume: 80,
+            },
+        };
+        let house = House {
+            name: "1号舍".to_string(),
+            code: "H01".to_string(),
+            enabled: true,
+            is_empty_mode: false,
+        };
+        let test = TestAlarmSnapshot {
+            duration: Some(20),
+            crontab: Some("0 0 8 * * * *".to_string()),
+        };
+        // 重复保存更新原记录
+        for _ in 0..2 {
+            for update in [
+                ConfigUpdate::Farm(farm.clone()),
+                ConfigUpdate::Soundposts(PostConfig {
+                    device_ids: vec![1, 2],
+                    speed: 60,
+                }),
+                ConfigUpdate::Houses(vec![house.clone()]),
+                ConfigUpdate::TestAlarm(test.clone()),
+            ] {
+                update.save(&db).await.unwrap();
+            }
+        }
+        ConfigUpdate::Soundposts(PostConfig {
+            device_ids: vec![2],
+            speed: 40,
+        })
+        .save(&db)
+        .await
+        .unwrap();
+
+        let snapshot = DbSnapshot::load(&db).await.unwrap();
+        assert_eq!(snapshot.houses, vec![house.clone()]);
+        assert_eq!(snapshot.farm.unwrap().soundbox.volume, 80);
+        assert_eq!(snapshot.soundposts.device_ids, vec![2]);
+        assert_eq!(snapshot.soundposts.speed, 40);
+        assert_eq!(snapshot.test_alarm.unwrap().crontab, test.crontab);
+
+        // 未下发的鸡舍停用
+        let other = House {
+            name: "2号舍".to_string(),
+            code: "H02".to_string(),
+            ..house
+        };
+        ConfigUpdate::Houses(vec![other.clone()])
+            .save(&db)
+            .await
+            .unwrap();
+        assert_eq!(DbSnapshot::load(&db).await.unwrap().houses, vec![other]);
+
+        // 暂存的配置写入数据库后移除
+        let path = format!("{}/pending_write_test.json", std::env::temp_dir().display());
+        let config: PersistConfig =
+            toml::from_str(&format!("mode = \"database\"\nstate_path = \"{path}\"")).unwrap();
+        let persist = ConfigPersist::open(&config).unwrap();
+        persist.save(ConfigUpdate::Soundposts(PostConfig {
+            device_ids: vec![5],
+            speed: 30,
+        }));
+        persist.write_pending(&db).await.unwrap();
+        assert!(!persist.has_pending());
+        assert_eq!(
+            DbSnapshot::load(&db).await.unwrap().soundposts.device_ids,
+            vec![5]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
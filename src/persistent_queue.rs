@@ -0,0 +1,373 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, mpsc},
+    thread,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::sync::{Notify, oneshot};
+use tracing::{error, info, warn};
+
+use crate::config::DropPolicy;
+
+/// 移除队首元素的标记行
+const POP_MARKER: &str = "-";
+/// 文件行数达到该值且超过队列长度两倍时压缩文件
+const COMPACT_MIN_LINES: usize = 256;
+
+struct QueueState<T> {
+    queue: VecDeque<T>,
+    dropped: u64,
+    // 文件中的行数，包括已移除元素及标记行
+    file_lines: usize,
+}
+
+/// 文件写入操作，由独立线程按顺序执行
+enum WriteOp {
+    Append(String),
+    Compact(Vec<String>),
+    Sync(oneshot::Sender<()>),
+}
+
+/// 持久化队列
+///
+/// 入队时追加元素行，出队时追加标记行，行数过多时整体重写压缩；文件写入在独立线程中进行，
+/// 不阻塞异步任务。进程重启后加载未处理的元素；文件路径为空时仅保存在内存中
+pub struct PersistentQueue<T> {
+    name: &'static str,
+    max_len: usize,
+    drop_policy: DropPolicy,
+    state: Arc<Mutex<QueueState<T>>>,
+    notify: Arc<Notify>,
+    writer: Option<mpsc::Sender<WriteOp>>,
+}
+
+impl<T> Clone for PersistentQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            max_len: self.max_len,
+            drop_policy: self.drop_policy.clone(),
+            state: self.state.clone(),
+            notify: self.notify.clone(),
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Debug + Clone> PersistentQueue<T> {
+    /// 打开队列，加载上次未处理的元素
+    pub fn open(
+        name: &'static str,
+        path: Option<String>,
+        max_len: usize,
+        drop_policy: DropPolicy,
+    ) -> anyhow::Result<Self> {
+        let Some(path) = path.map(PathBuf::from) else {
+            return Ok(Self::memory(name, max_len, drop_policy));
+        };
+
+        // 无效行以 None 占位，保证之后的标记行移除的仍是对应的元素
+        let mut queue: VecDeque<Option<T>> = VecDeque::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.split(b'\n') {
+                let line = match String::from_utf8(line?) {
+                    Ok(line) => line,
+                    Err(e) => {
+                        warn!("Invalid {name} item: {e}, skipped.");
+                        queue.push_back(None);
+                        continue;
+                    }
+                };
+                let line = line.trim_end_matches('\r');
+                if line == POP_MARKER {
+                    queue.pop_front();
+                    continue;
+                }
+                match serde_json::from_str::<T>(line) {
+                    Ok(item) => queue.push_back(Some(item)),
+                    Err(e) => {
+                        warn!("Invalid {name} item: {line}, err: {e}, skipped.");
+                        queue.push_back(None);
+                    }
+                }
+            }
+            info!(
+                "Loaded {} pending items from {name}",
+                queue.iter().flatten().count()
+            );
+        }
+
+        let loaded: VecDeque<T> = queue.into_iter().flatten().collect();
+        let mut queue = Self::memory(name, max_len, drop_policy);
+        queue.writer = Some(spawn_writer(name, path));
+        {
+            // 加载的元素可能超出新配置的上限
+            let mut state = queue.lock();
+            state.queue = loaded;
+            while state.queue.len() > queue.max_len {
+                state.queue.pop_front();
+                state.dropped += 1;
+            }
+            // 加载后压缩，去除标记行及无效行
+            queue.compact(&mut state);
+        }
+
+        Ok(queue)
+    }
+
+    /// 仅保存在内存中的队列
+    pub fn memory(name: &'static str, max_len: usize, drop_policy: DropPolicy) -> Self {
+        Self {
+            name,
+            max_len: max_len.max(1),
+            drop_policy,
+            state: Arc::new(Mutex::new(QueueState {
+                queue: VecDeque::new(),
+                dropped: 0,
+                file_lines: 0,
+            })),
+            notify: Arc::new(Notify::new()),
+            writer: None,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 加入元素，返回是否入队
+    pub fn push(&self, item: T) -> bool {
+        let accepted = {
+            let mut state = self.lock();
+            let accepted = if state.queue.len() < self.max_len {
+                true
+            } else {
+                state.dropped += 1;
+                match self.drop_policy {
+                    DropPolicy::DropOldest => {
+                        if let Some(dropped) = state.queue.pop_front() {
+                            warn!("{} full, drop oldest: {:?}", self.name, dropped);
+                            self.append(&mut state, POP_MARKER.to_string());
+                        }
+                        true
+                    }
+                    DropPolicy::DropNewest => {
+                        warn!("{} full, drop: {:?}", self.name, item);
+                        false
+                    }
+                }
+            };
+            if accepted {
+                if self.writer.is_some() {
+                    match serde_json::to_string(&item) {
+                        Ok(line) => self.append(&mut state, line),
+                        Err(e) => error!("Serialize {} item: {:?} failed: {e}", self.name, item),
+                    }
+                }
+                state.queue.push_back(item);
+            }
+            accepted
+        };
+
+        if accepted {
+            self.notify.notify_one();
+        }
+        accepted
+    }
+
+    /// 最早的元素
+    pub fn front(&self) -> Option<T> {
+        self.lock().queue.front().cloned()
+    }
+
+    /// 移除已处理的元素
+    pub fn pop_front(&self) {
+        let mut state = self.lock();
+        if state.queue.pop_front().is_some() {
+            self.append(&mut state, POP_MARKER.to_string());
+            if state.file_lines >= COMPACT_MIN_LINES && state.file_lines > state.queue.len() * 2 {
+                self.compact(&mut state);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 因队列满丢弃的元素数
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    /// 等待新元素入队
+    pub async fn notified(&self) {
+        self.notify.notified().await;
+    }
+
+    /// 等待已提交的写入完成，退出前调用
+    pub async fn sync(&self) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let (tx, rx) = oneshot::channel();
+        if writer.send(WriteOp::Sync(tx)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    fn append(&self, state: &mut QueueState<T>, line: String) {
+        if let Some(writer) = &self.writer {
+            state.file_lines += 1;
+            let _ = writer.send(WriteOp::Append(line));
+        }
+    }
+
+    /// 按当前队列重写文件
+    fn compact(&self, state: &mut QueueState<T>) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        let lines: Vec<String> = state
+            .queue
+            .iter()
+            .filter_map(|item| serde_json::to_string(item).ok())
+            .collect();
+        state.file_lines = lines.len();
+        let _ = writer.send(WriteOp::Compact(lines));
+    }
+}
+
+/// 启动文件写入线程，所有队列句柄释放后退出
+fn spawn_writer(name: &'static str, path: PathBuf) -> mpsc::Sender<WriteOp> {
+    let (tx, rx) = mpsc::channel::<WriteOp>();
+    let spawned = thread::Builder::new()
+        .name(format!("{name} writer"))
+        .spawn(move || {
+            let mut file: Option<BufWriter<File>> = None;
+            while let Ok(op) = rx.recv() {
+                // 合并积压的写入，每批只同步一次
+                let mut syncs = Vec::new();
+                for op in std::iter::once(op).chain(rx.try_iter()) {
+                    let result = match op {
+                        WriteOp::Append(line) => append_line(&path, &mut file, &line),
+                        WriteOp::Compact(lines) => {
+                            file = None;
+                            rewrite(&path, &lines)
+                        }
+                        WriteOp::Sync(tx) => {
+                            syncs.push(tx);
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        error!("Persist {name}: {} failed: {e}", path.display());
+                    }
+                }
+                if let Some(writer) = file.as_mut()
+                    && let Err(e) = writer.flush().and_then(|_| writer.get_ref().sync_data())
+                {
+                    error!("Persist {name}: {} failed: {e}", path.display());
+                }
+                for tx in syncs {
+                    let _ = tx.send(());
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Start {name} writer failed: {e}");
+    }
+    tx
+}
+
+fn append_line(
+    path: &PathBuf,
+    file: &mut Option<BufWriter<File>>,
+    line: &str,
+) -> anyhow::Result<()> {
+    if file.is_none() {
+        let opened = OpenOptions::new().create(true).append(true).open(path)?;
+        *file = Some(BufWriter::new(opened));
+    }
+    if let Some(writer) = file.as_mut() {
+        writeln!(writer, "{line}")?;
+    }
+    Ok(())
+}
+
+/// 写入临时文件后替换，避免写入中断导致文件损坏
+fn rewrite(path: &PathBuf, lines: &[String]) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    for line in lines {
+        writeln!(writer, "{line}")?;
+    }
+    writer.into_inner()?.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod persistent_queue_tests {
+    use crate::config::DropPolicy;
+
+    use super::{COMPACT_MIN_LINES, PersistentQueue};
+
+    fn open(path: &str) -> PersistentQueue<u32> {
+        PersistentQueue::open(
+            "test queue",
+            Some(path.to_string()),
+            1000,
+            DropPolicy::DropOldest,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_append_and_compact() {
+        let path = format!("{}/persistent_queue.jsonl", std::env::temp_dir().display());
+        let _ = std::fs::remove_file(&path);
+
+        let queue = open(&path);
+        for i in 0..3 {
+            queue.push(i);
+        }
+        queue.pop_front();
+        queue.sync().await;
+        // 出队仅追加标记行
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0\n1\n2\n-\n");
+
+        let queue = open(&path);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.front(), Some(1));
+        queue.sync().await;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1\n2\n");
+
+        for i in 0..COMPACT_MIN_LINES as u32 {
+            queue.push(i);
+            queue.pop_front();
+        }
+        queue.sync().await;
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines < COMPACT_MIN_LINES);
+        assert_eq!(open(&path).len(), 2);
+
+        // 非 UTF-8 行及无法解析的行跳过，之后的标记行仍移除对应的元素
+        std::fs::write(&path, b"1\n\xff\nx\n2\n3\n-\n-\n-\n-\n").unwrap();
+        let queue = open(&path);
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.front(), Some(3));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -11,10 +11,18 @@ pub enum PlayCancelType {
     Terminated,
 }
 
+/// 未播放原因
+#[derive(Debug, Clone)]
+pub enum PlaySkipType {
+    AlarmOngoing,
+    NoOutput,
+}
+
 /// 播放结果类型
 #[derive(Debug, Clone)]
 pub enum PlayResultType {
     Normal,
     Timeout,
     Canceled(PlayCancelType),
+    Skipped(PlaySkipType),
 }
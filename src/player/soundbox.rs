@@ -1,6 +1,6 @@
 use std::{fs::File, io::BufReader, sync::Arc, time::Duration};
 
-use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, source::Buffered};
+use rodio::{Decoder, OutputStream, OutputStreamBuilder, Sink, Source, source::Buffered};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info};
 
@@ -16,6 +16,24 @@ impl Soundbox {
         Self(duration)
     }
 
+    /// 加载并解码媒体文件，返回缓冲及时长
+    pub fn load_media(path: &str) -> anyhow::Result<(Buffer, Duration)> {
+        let file =
+            File::open(path).map_err(|e| anyhow::anyhow!("Open media: {path} failed: {e}"))?;
+        let buffer = Decoder::try_from(file)
+            .map_err(|e| anyhow::anyhow!("Decode media: {path} failed: {e}"))?
+            .buffered();
+
+        // 完整解码一次，同时得出实际时长
+        let samples = buffer.clone().count() as u64;
+        let rate = buffer.sample_rate() as u64 * buffer.channels() as u64;
+        if samples == 0 || rate == 0 {
+            anyhow::bail!("Media: {path} is empty");
+        }
+
+        Ok((buffer, Duration::from_millis(samples * 1000 / rate)))
+    }
+
     fn create_sink() -> anyhow::Result<(OutputStream, Sink)> {
         let handler = OutputStreamBuilder::open_default_stream()
             .inspect_err(|e| error!("Failed open default stream: {e}"))?;
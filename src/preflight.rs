@@ -0,0 +1,379 @@
+use std::{fmt::Display, fs, str::FromStr, time::Duration};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cron::Schedule;
+use tracing::{error, info, warn};
+
+use crate::{
+    Recorder,
+    config::{Config, InputSource, PlayMode},
+    mqtt_client::MqttClient,
+    player::Soundbox,
+    service::{DbSnapshot, Localization, connect_db},
+    task::WsClient,
+    topic::Topics,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Fail => "FAIL",
+            CheckStatus::Skip => "SKIP",
+        };
+        write!(f, "{status}")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    // 必需项失败时无法启动，其余项失败时降级运行
+    pub required: bool,
+}
+
+impl CheckResult {
+    fn from_result(name: &'static str, required: bool, result: anyhow::Result<String>) -> Self {
+        let (status, detail) = match result {
+            Ok(detail) => (CheckStatus::Pass, detail),
+            Err(e) => (CheckStatus::Fail, e.to_string()),
+        };
+        Self {
+            name,
+            status,
+            detail,
+            required,
+        }
+    }
+
+    fn skip(name: &'static str, detail: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skip,
+            detail: detail.to_string(),
+            required: false,
+        }
+    }
+}
+
+/// 启动前检查报告
+#[derive(Debug, Default)]
+pub struct Report {
+    pub results: Vec<CheckResult>,
+}
+
+impl Report {
+    fn count(&self, status: CheckStatus) -> usize {
+        self.results.iter().filter(|r| r.status == status).count()
+    }
+
+    /// 全部检查通过
+    pub fn passed(&self) -> bool {
+        self.count(CheckStatus::Fail) == 0
+    }
+
+    /// 必需项检查失败
+    pub fn required_failed(&self) -> bool {
+        self.results
+            .iter()
+            .any(|r| r.required && r.status == CheckStatus::Fail)
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} passed, {} failed, {} skipped",
+            self.count(CheckStatus::Pass),
+            self.count(CheckStatus::Fail),
+            self.count(CheckStatus::Skip)
+        )
+    }
+
+    /// 输出到标准输出，用于 --check
+    pub fn print(&self) {
+        for r in &self.results {
+            let required = if r.required { "*" } else { " " };
+            println!("[{}] {}{:<14} {}", r.status, required, r.name, r.detail);
+        }
+        println!("Preflight: {} (* required)", self.summary());
+    }
+
+    /// 输出到日志，用于启动检查
+    pub fn log(&self) {
+        for r in &self.results {
+            match r.status {
+                CheckStatus::Pass => info!("Preflight {}: {}", r.name, r.detail),
+                CheckStatus::Skip => info!("Preflight {} skipped: {}", r.name, r.detail),
+                CheckStatus::Fail if r.required => {
+                    error!("Preflight {} failed: {}", r.name, r.detail)
+                }
+                CheckStatus::Fail => warn!("Preflight {} failed: {}", r.name, r.detail),
+            }
+        }
+        info!("Preflight: {}", self.summary());
+    }
+}
+
+/// 检查配置、媒体文件、音频设备、数据库、MQTT 代理、音柱服务、本地化文件及测试报警计划
+pub async fn run(config: &Config, localization_path: &str) -> Report {
+    let timeout = Duration::from_secs(config.preflight.timeout_secs().max(1));
+    let mut report = Report::default();
+    let results = &mut report.results;
+
+    results.push(CheckResult::from_result(
+        "config",
+        true,
+        check_config(config),
+    ));
+    results.push(CheckResult::from_result(
+        "alarm_media",
+        true,
+        check_media(&config.soundbox.alarm_media_path()),
+    ));
+    results.push(CheckResult::from_result(
+        "test_media",
+        true,
+        check_media(&config.soundbox.test_media_path()),
+    ));
+    results.push(CheckResult::from_result(
+        "audio_output",
+        false,
+        check_output(),
+    ));
+    results.push(CheckResult::from_result(
+        "audio_input",
+        false,
+        Recorder::new(config.recorder.clone()).check_input(),
+    ));
+    results.push(CheckResult::from_result(
+        "localization",
+        false,
+        check_localization(localization_path, &config.alarm.default_langauge()),
+    ));
+
+    // 测试报警计划来自数据库，数据库不可用时使用本地缓存；仅连接及读取，不执行迁移
+    let connect = async {
+        let db = connect_db(&config.database).await?;
+        db.ping().await?;
+        anyhow::Ok(db)
+    };
+    let snapshot = match tokio::time::timeout(timeout, connect).await {
+        Ok(Ok(db)) => {
+            let snapshot = DbSnapshot::load(&db).await;
+            let _ = db.close().await;
+            results.push(CheckResult::from_result(
+                "database",
+                false,
+                snapshot
+                    .as_ref()
+                    .map(|s| format!("connected, {} houses", s.houses.len()))
+                    .map_err(|e| anyhow::anyhow!("Load config failed: {e}")),
+            ));
+            snapshot.ok()
+        }
+        Ok(Err(e)) => {
+            results.push(CheckResult::from_result("database", false, Err(e)));
+            DbSnapshot::read_cache(config.database.cache_path())
+                .ok()
+                .flatten()
+        }
+        Err(_) => {
+            results.push(CheckResult::from_result(
+                "database",
+                false,
+                Err(anyhow::anyhow!("Connect timeout after {:?}", timeout)),
+            ));
+            DbSnapshot::read_cache(config.database.cache_path())
+                .ok()
+                .flatten()
+        }
+    };
+    let crontab = snapshot.and_then(|s| s.test_alarm).and_then(|t| t.crontab);
+    results.push(match crontab {
+        Some(crontab) => CheckResult::from_result("crontab", false, check_crontab(&crontab)),
+        None => CheckResult::skip("crontab", "not configured"),
+    });
+
+    results.push(CheckResult::from_result(
+        "mqtt",
+        false,
+        MqttClient::check_broker(&config.mqtt, timeout)
+            .await
+            .map(|_| {
+                format!(
+                    "connected to {}:{}",
+                    config.mqtt.broker(),
+                    config.mqtt.port()
+                )
+            }),
+    ));
+
+    if config.soundpost.enabled() {
+        let api_host = config.soundpost.api_host();
+        let username = config.soundpost.ws_username();
+        let password = config.soundpost.ws_password();
+        let login = WsClient::login(&api_host, &username, &password);
+        results.push(CheckResult::from_result(
+            "soundpost",
+            false,
+            match tokio::time::timeout(timeout, login).await {
+                Ok(result) => result.map(|_| format!("logged in to {api_host}")),
+                Err(_) => Err(anyhow::anyhow!("Login timeout after {:?}", timeout)),
+            },
+        ));
+    } else {
+        results.push(CheckResult::skip("soundpost", "disabled"));
+    }
+
+    report
+}
+
+/// 检查无法在运行时恢复的配置值
+fn check_config(config: &Config) -> anyhow::Result<String> {
+    let mut errors = Vec::new();
+    if let Err(e) = Topics::new(&config.topic) {
+        errors.push(e.to_string());
+    }
+    if config.mqtt.broker().is_empty() || config.mqtt.port() == 0 {
+        errors.push("mqtt broker and port are required".to_string());
+    }
+    let missing = config.missing_secrets();
+    if !missing.is_empty() {
+        errors.push(format!("missing secrets: {}", missing.join(", ")));
+    }
+    if config.soundpost.enabled() && config.soundpost.api_host().is_empty() {
+        errors.push("soundpost api_host is required".to_string());
+    }
+    if matches!(config.soundpost.play_mode(), PlayMode::Music)
+        && (config.soundpost.alarm_media_url().is_empty()
+            || config.soundpost.test_media_url().is_empty())
+    {
+        errors.push("soundpost media urls are required in music mode".to_string());
+    }
+    if config.alarm.default_langauge().is_empty() {
+        errors.push("alarm default_language is required".to_string());
+    }
+    if matches!(config.recorder.input_source(), InputSource::File)
+        && config.recorder.input_file().is_none()
+    {
+        errors.push("recorder input_file is required for file input".to_string());
+    }
+    // 队列长度为 0 时创建通道会 panic
+    let queue = &config.queue;
+    for (name, size) in [
+        ("act_alarm_size", queue.act_alarm_size()),
+        ("test_alarm_size", queue.test_alarm_size()),
+        ("cycle_alarm_size", queue.cycle_alarm_size()),
+        ("realtime_play_size", queue.realtime_play_size()),
+        ("cycle_play_size", queue.cycle_play_size()),
+        ("broadcast_size", queue.broadcast_size()),
+    ] {
+        if size == 0 {
+            errors.push(format!("queue {name} must be greater than 0"));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok("ok".to_string())
+    } else {
+        anyhow::bail!(errors.join("; "))
+    }
+}
+
+fn check_media(path: &str) -> anyhow::Result<String> {
+    let (_, duration) = Soundbox::load_media(path)?;
+    Ok(format!("{path}, {:.1}s", duration.as_secs_f32()))
+}
+
+fn check_output() -> anyhow::Result<String> {
+    let device = match cpal::default_host().default_output_device() {
+        Some(device) => device,
+        None => anyhow::bail!("No default output device found."),
+    };
+    let config = device.default_output_config()?;
+    Ok(format!(
+        "{}, {} Hz, {} channels",
+        device.name().unwrap_or_default(),
+        config.sample_rate().0,
+        config.channels()
+    ))
+}
+
+/// 本地化文件均可解析，且包含默认语言
+fn check_localization(path: &str, default_language: &str) -> anyhow::Result<String> {
+    let mut cultures = Vec::new();
+    let entries =
+        fs::read_dir(path).map_err(|e| anyhow::anyhow!("Read directory: {path} failed: {e}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let localization: Localization = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow::anyhow!("Parse file: {} failed: {e}", path.display()))?;
+        cultures.push(localization.culture);
+    }
+
+    if !cultures.iter().any(|c| c == default_language) {
+        anyhow::bail!("Default language: {default_language} not found in {path}");
+    }
+    cultures.sort();
+    Ok(cultures.join(", "))
+}
+
+fn check_crontab(crontab: &str) -> anyhow::Result<String> {
+    let schedule = Schedule::from_str(crontab)
+        .map_err(|e| anyhow::anyhow!("Invalid crontab: {crontab}, err: {e}"))?;
+    match schedule.upcoming(chrono::Utc).next() {
+        Some(next) => Ok(format!("{crontab}, next at {next}")),
+        None => anyhow::bail!("Crontab: {crontab} never fires"),
+    }
+}
+
+#[cfg(test)]
+mod preflight_tests {
+    use crate::config::Config;
+
+    use super::{check_config, check_crontab, check_localization};
+
+    #[test]
+    fn test_check_values() {
+        let secrets = "[database]\nconnection = \"sqlite::memory:\"\n\
+                       [soundpost]\napi_login_token = \"t\"\nws_username = \"u\"\nws_password = \"p\"\n";
+        let config: Config =
+            toml::from_str(&format!("{secrets}[queue]\nbroadcast_size = 0")).unwrap();
+        let err = check_config(&config).unwrap_err().to_string();
+        assert!(err.contains("broadcast_size"));
+        assert!(check_config(&toml::from_str(secrets).unwrap()).is_ok());
+
+        let err = check_config(&Config::default()).unwrap_err().to_string();
+        assert!(err.contains("database.connection"));
+
+        assert!(check_crontab("0 0 8 * * * *").is_ok());
+        assert!(check_crontab("0 0 25 * * * *").is_err());
+
+        let dir = std::env::temp_dir().join("preflight_localization");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("en.json"),
+            r#"{"culture":"en","texts":{"Fire":"Fire"}}"#,
+        )
+        .unwrap();
+        let path = dir.to_str().unwrap();
+        assert_eq!(check_localization(path, "en").unwrap(), "en");
+        assert!(check_localization(path, "zh-Hans").is_err());
+
+        std::fs::write(dir.join("bad.json"), "{").unwrap();
+        assert!(check_localization(path, "en").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+use std::{sync::Arc, time::Duration};
+
+use sea_orm::{DatabaseConnection, DbErr, RuntimeErr, sqlx};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::{io::AsyncWriteExt, sync::Notify};
+use tracing::{error, info, warn};
+
+use crate::{
+    Service,
+    config::{DropPolicy, RecordQueueConfig},
+    model::{alarm_event_history, alarm_play_record, test_alarm_play_record},
+    persistent_queue::PersistentQueue,
+};
+
+/// 待写入数据库的播放记录及报警事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "table", content = "record")]
+pub enum PendingRecord {
+    #[serde(rename = "alarm_play_record")]
+    AlarmPlay(alarm_play_record::Model),
+    #[serde(rename = "test_alarm_play_record")]
+    TestAlarmPlay(test_alarm_play_record::Model),
+    #[serde(rename = "alarm_event_history")]
+    AlarmEvent(alarm_event_history::Model),
+}
+
+impl PendingRecord {
+    async fn insert(self, db: &DatabaseConnection) -> anyhow::Result<()> {
+        match self {
+            PendingRecord::AlarmPlay(model) => alarm_play_record::insert(model, db).await,
+            PendingRecord::TestAlarmPlay(model) => test_alarm_play_record::insert(model, db).await,
+            PendingRecord::AlarmEvent(model) => alarm_event_history::insert(model, db).await,
+        }
+    }
+}
+
+/// 数据库拒绝写入的记录，重试无效：约束冲突、数据错误及表结构不符
+fn is_rejected(e: &anyhow::Error) -> bool {
+    let Some(err) = e.downcast_ref::<DbErr>() else {
+        return false;
+    };
+    if err.sql_err().is_some() {
+        return true;
+    }
+    let (DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))
+    | DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Database(db_err)))) = err
+    else {
+        return false;
+    };
+    if !matches!(db_err.kind(), sqlx::error::ErrorKind::Other) {
+        return true;
+    }
+    // PostgreSQL 22: 数据错误 42: 语法错误或表、字段不存在；SQLite 1: SQL 错误或表不存在
+    db_err
+        .code()
+        .is_some_and(|code| code.starts_with("22") || code.starts_with("42") || code == "1")
+}
+
+/// 播放记录写入队列
+///
+/// 播放记录先写入本地文件，由后台任务按顺序写入数据库；数据库不可用时退避重试，
+/// 被数据库拒绝的记录写入死信文件后跳过，播放流程不等待数据库
+#[derive(Clone)]
+pub struct RecordQueue {
+    name: &'static str,
+    queue: PersistentQueue<PendingRecord>,
+    retry_min: Duration,
+    retry_max: Duration,
+    dead_letter_path: Option<String>,
+    // 数据库重新连接的通知，结束退避等待
+    connected: Arc<Notify>,
+}
+
+impl Default for RecordQueue {
+    fn default() -> Self {
+        let config = RecordQueueConfig::default();
+        Self {
+            name: "record queue",
+            queue: PersistentQueue::memory(
+                "record queue",
+                config.max_records(),
+                DropPolicy::DropOldest,
+            ),
+            retry_min: Duration::from_secs(config.retry_min_secs()),
+            retry_max: Duration::from_secs(config.retry_max_secs()),
+            dead_letter_path: None,
+            connected: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl RecordQueue {
+    /// 打开播放记录队列，加载上次未写入的记录
+    pub fn open(config: &RecordQueueConfig) -> anyhow::Result<Self> {
+        Self::open_queue(
+            "record queue",
+            config.path(),
+            config.max_records(),
+            config.drop_policy(),
+            config,
+        )
+    }
+
+    /// 打开报警事件队列，事件量大时只丢弃最早的事件，不挤占播放记录
+    pub fn open_events(config: &RecordQueueConfig) -> anyhow::Result<Self> {
+        Self::open_queue(
+            "event queue",
+            config.event_path(),
+            config.max_events(),
+            DropPolicy::DropOldest,
+            config,
+        )
+    }
+
+    fn open_queue(
+        name: &'static str,
+        path: Option<String>,
+        max_len: usize,
+        drop_policy: DropPolicy,
+        config: &RecordQueueConfig,
+    ) -> anyhow::Result<Self> {
+        let retry_min = Duration::from_secs(config.retry_min_secs().max(1));
+        Ok(Self {
+            name,
+            queue: PersistentQueue::open(name, path, max_len, drop_policy)?,
+            retry_min,
+            retry_max: Duration::from_secs(config.retry_max_secs()).max(retry_min),
+            dead_letter_path: config.dead_letter_path(),
+            connected: Arc::new(Notify::new()),
+        })
+    }
+
+    pub fn push(&self, record: PendingRecord) -> bool {
+        self.queue.push(record)
+    }
+
+    /// 未写入的记录数
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// 等待队列文件写入完成
+    pub async fn sync(&self) {
+        self.queue.sync().await;
+    }
+
+    /// 数据库已连接，立即重试写入
+    pub fn db_connected(&self) {
+        self.connected.notify_one();
+    }
+
+    /// 下一次重试间隔
+    fn backoff(&self, current: Duration) -> Duration {
+        (current * 2).min(self.retry_max)
+    }
+
+    /// 将被拒绝的记录追加到死信文件
+    async fn dead_letter(&self, record: &PendingRecord, e: &anyhow::Error) {
+        error!("{} record rejected: {e}, record: {:?}", self.name, record);
+        let Some(path) = &self.dead_letter_path else {
+            return;
+        };
+
+        let write = async {
+            let mut line = serde_json::to_value(record)?;
+            if let Value::Object(map) = &mut line {
+                map.insert("error".to_string(), json!(e.to_string()));
+                map.insert(
+                    "timestamp".to_string(),
+                    json!(OffsetDateTime::now_utc().format(&Rfc3339)?),
+                );
+            }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            file.write_all(format!("{line}\n").as_bytes()).await?;
+            file.sync_data().await?;
+            anyhow::Ok(())
+        };
+        if let Err(e) = write.await {
+            error!("Write dead letter: {path} failed: {e}");
+        }
+    }
+
+    /// 按顺序将记录写入数据库，数据库未连接或写入失败时退避重试，重新连接后立即写入，被拒绝的记录移出队列
+    pub async fn flush(&self, service: Service, shutdown: Arc<Notify>) {
+        // 提前注册，避免写入数据库期间错过停止通知
+        let stopped = shutdown.notified();
+        tokio::pin!(stopped);
+        stopped.as_mut().enable();
+
+        let mut retry = self.retry_min;
+        loop {
+            if self.queue.is_empty() {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = self.queue.notified() => {}
+                }
+                continue;
+            }
+
+            let Some(record) = self.queue.front() else {
+                continue;
+            };
+            let db = service.read().await.db.clone();
+            let result = match db {
+                Some(db) => tokio::select! {
+                    _ = &mut stopped => break,
+                    result = record.clone().insert(&db) => result,
+                },
+                None => Err(anyhow::anyhow!("Database is not connected")),
+            };
+
+            match result {
+                Ok(_) => {
+                    self.queue.pop_front();
+                    retry = self.retry_min;
+                }
+                Err(e) if is_rejected(&e) => {
+                    self.dead_letter(&record, &e).await;
+                    self.queue.pop_front();
+                }
+                Err(e) => {
+                    warn!(
+                        "Write {} failed: {e}, {} pending, retry in {:?}",
+                        self.name,
+                        self.queue.len(),
+                        retry
+                    );
+                    tokio::select! {
+                        _ = &mut stopped => break,
+                        _ = tokio::time::sleep(retry) => retry = self.backoff(retry),
+                        _ = self.connected.notified() => retry = self.retry_min,
+                    }
+                }
+            }
+        }
+
+        if self.queue.dropped() > 0 {
+            error!(
+                "{} records dropped as {} full",
+                self.queue.dropped(),
+                self.name
+            );
+        }
+        info!(
+            "Stop {} flushing, {} records pending.",
+            self.name,
+            self.queue.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod record_queue_tests {
+    use std::time::Duration;
+
+    use crate::config::RecordQueueConfig;
+
+    use super::RecordQueue;
+
+    #[test]
+    fn test_backoff() {
+        let config: RecordQueueConfig =
+            toml::from_str("path = \"\"\nretry_min_secs = 2\nretry_max_secs = 10").unwrap();
+        let queue = RecordQueue::open(&config).unwrap();
+
+        let mut retry = queue.retry_min;
+        let mut retries = Vec::new();
+        for _ in 0..4 {
+            retries.push(retry.as_secs());
+            retry = queue.backoff(retry);
+        }
+        assert_eq!(retries, vec![2, 4, 8, 10]);
+        assert_eq!(queue.backoff(Duration::from_secs(10)).as_secs(), 10);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_dead_letter() {
+        use std::sync::Arc;
+
+        use sea_orm::Database;
+        use tokio::sync::{Notify, RwLock};
+
+        use crate::{
+            model::alarm_event_history, record_queue::PendingRecord, service::AlarmService,
+        };
+
+        let path = format!("{}/dead_records.jsonl", std::env::temp_dir().display());
+        let _ = std::fs::remove_file(&path);
+        let config: RecordQueueConfig = toml::from_str(&format!(
+            "path = \"\"\nretry_min_secs = 1\ndead_letter_path = \"{path}\""
+        ))
+        .unwrap();
+        let queue = RecordQueue::open(&config).unwrap();
+        let now = time::OffsetDateTime::now_utc();
+        let now = time::PrimitiveDateTime::new(now.date(), now.time());
+        queue.push(PendingRecord::AlarmEvent(alarm_event_history::Model {
+            id: uuid::Uuid::new_v4(),
+            house_code: "H01".to_string(),
+            target_name: "1号舍".to_string(),
+            alarm_item: "温度".to_string(),
+            event_type: "received".to_string(),
+            reason: None,
+            alarm_time: now,
+            creation_time: now,
+        }));
+
+        // 表不存在时记录写入死信文件，不阻塞后续记录
+        let service = Arc::new(RwLock::new(AlarmService::default()));
+        service.write().await.db = Some(Database::connect("sqlite::memory:").await.unwrap());
+        let shutdown = Arc::new(Notify::new());
+        let flush = {
+            let queue = queue.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move { queue.flush(service, shutdown).await })
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !queue.is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        shutdown.notify_waiters();
+        flush.await.unwrap();
+
+        let dead = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(dead.lines().count(), 1);
+        assert!(dead.contains("alarm_event_history"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_flush_after_connect() {
+        use std::sync::Arc;
+
+        use sea_orm::Database;
+        use sea_orm_migration::MigratorTrait;
+        use tokio::sync::{Notify, RwLock};
+
+        use crate::{
+            migration::Migrator, model::test_alarm_play_record, record_queue::PendingRecord,
+            service::AlarmService,
+        };
+
+        // 退避间隔远大于等待时间，重新连接后立即写入
+        let config: RecordQueueConfig =
+            toml::from_str("path = \"\"\nretry_min_secs = 300").unwrap();
+        let queue = RecordQueue::open(&config).unwrap();
+        let now = time::OffsetDateTime::now_utc();
+        let now = time::PrimitiveDateTime::new(now.date(), now.time());
+        queue.push(PendingRecord::TestAlarmPlay(
+            test_alarm_play_record::Model {
+                id: uuid::Uuid::new_v4(),
+                plan_time: now,
+                test_time: now,
+                test_type: 1,
+                notify_obj: None,
+                media_file: None,
+                test_result: 1,
+                has_error: false,
+                err_message: None,
+                creation_time: now,
+            },
+        ));
+
+        // 数据库未连接时记录保留在队列中
+        let service = Arc::new(RwLock::new(AlarmService::default()));
+        service
+            .write()
+            .await
+            .set_record_queue(queue.clone(), RecordQueue::default());
+        let shutdown = Arc::new(Notify::new());
+        let flush = {
+            let queue = queue.clone();
+            let service = service.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move { queue.flush(service, shutdown).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queue.len(), 1);
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        service.write().await.set_db(db);
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !queue.is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        shutdown.notify_waiters();
+        flush.await.unwrap();
+    }
+}
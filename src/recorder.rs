@@ -1,143 +1,619 @@
 use std::{
+    collections::HashMap,
     fs::File,
     io::BufWriter,
     os::unix::fs,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use cpal::{
-    FromSample, Sample,
+    FromSample, Sample, SizedSample,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-type WavWriterHandle = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
+use crate::config::{InputSource, RecordFormat, RecorderConfig};
+
+mod convert;
+use convert::Converter;
+
+mod flac;
+use flac::FlacWriter;
+
+mod level;
+use level::LevelMeter;
+pub use level::RecordLevel;
+
+mod meta;
+pub use meta::{RecordMeta, RecordQuery};
+
+mod ring;
+use ring::RingBuffer;
+
+mod source;
+use source::FileStream;
+pub use source::InputStream;
+
+/// 录音文件写入器，统一输出 16 bit 采样
+enum RecordWriter {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter),
+}
+
+impl RecordWriter {
+    fn create(
+        format: &RecordFormat,
+        path: &str,
+        sample_rate: u32,
+        channels: u16,
+    ) -> anyhow::Result<Self> {
+        let writer = match format {
+            RecordFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels,
+                    sample_rate,
+                    bits_per_sample: 16,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                Self::Wav(hound::WavWriter::create(path, spec)?)
+            }
+            RecordFormat::Flac => Self::Flac(FlacWriter::create(path, sample_rate, channels)?),
+        };
+
+        Ok(writer)
+    }
+
+    fn write_sample(&mut self, sample: i16) -> anyhow::Result<()> {
+        match self {
+            Self::Wav(writer) => writer.write_sample(sample)?,
+            Self::Flac(writer) => writer.write_sample(sample)?,
+        }
+
+        Ok(())
+    }
+
+    fn finalize(self) -> anyhow::Result<()> {
+        match self {
+            Self::Wav(writer) => writer.finalize()?,
+            Self::Flac(writer) => writer.finalize()?,
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Capture {
+    writer: RecordWriter,
+    converter: Converter,
+    meter: LevelMeter,
+}
+
+impl Capture {
+    /// 写入录音输入，同时统计电平
+    fn write(&mut self, input: &[f32]) {
+        let Self {
+            writer,
+            converter,
+            meter,
+        } = self;
+        for &sample in input.iter() {
+            meter.feed(sample);
+            converter.push(sample, |s| {
+                writer.write_sample(i16::from_sample(s)).ok();
+            });
+        }
+    }
+
+    /// 输出重采样缓存中的剩余音频并关闭录音文件
+    fn finalize(self) -> anyhow::Result<()> {
+        let Self {
+            mut writer,
+            mut converter,
+            ..
+        } = self;
+        converter.finish(|s| {
+            writer.write_sample(i16::from_sample(s)).ok();
+        });
+        writer.finalize()
+    }
+
+    /// 写入预录音频，不参与电平统计
+    fn write_pre_roll(&mut self, input: impl Iterator<Item = f32>) {
+        let Self {
+            writer, converter, ..
+        } = self;
+        for sample in input {
+            converter.push(sample, |s| {
+                writer.write_sample(i16::from_sample(s)).ok();
+            });
+        }
+    }
+}
+
+type CaptureHandle = Arc<Mutex<Option<Capture>>>;
+
+/// 常开的预录输入，缓存最近的音频，并分发给挂接的录音
+struct Monitor {
+    _stream: InputStream,
+    sample_rate: u32,
+    channels: u16,
+    state: Arc<Mutex<MonitorState>>,
+}
+
+struct MonitorState {
+    ring: RingBuffer,
+    captures: Vec<CaptureHandle>,
+    // 正在写入预录音频的录音，期间的输入先暂存，写完后补写
+    staging: HashMap<u64, Vec<f32>>,
+    next_staging_id: u64,
+}
+
+impl MonitorState {
+    fn feed(&mut self, input: &[f32]) {
+        self.ring.push(input);
+        for staged in self.staging.values_mut() {
+            staged.extend_from_slice(input);
+        }
+        for capture in self.captures.iter() {
+            if let Ok(mut guard) = capture.lock()
+                && let Some(capture) = guard.as_mut()
+            {
+                capture.write(input);
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Recorder {
     storage_path: String,
     link_path: String,
+    // 可闻判定阈值，单位 dBFS
+    audible_threshold_db: f32,
+    // 超过阈值的最小时长占比
+    audible_min_ratio: f32,
+    // 录音文件格式
+    format: RecordFormat,
+    // 是否混缩为单声道
+    mono: bool,
+    // 录音输出采样率，未设置时使用设备采样率
+    sample_rate: Option<u32>,
+    // 录音输入源
+    input_source: InputSource,
+    // 输入设备名称
+    input_device: Option<String>,
+    // 输入采样率
+    input_sample_rate: Option<u32>,
+    // 输入声道数
+    input_channels: Option<u16>,
+    // 文件输入源路径
+    input_file: Option<String>,
+    // 预录时长，单位 ms
+    pre_roll_millis: u64,
+    // 停止后继续录制的时长，单位 ms
+    post_roll_millis: u64,
+    // 常开的预录输入，首次使用时启动
+    monitor: Arc<Mutex<Option<Monitor>>>,
 }
 
 impl Recorder {
-    pub fn new(storage_path: String, link_path: String) -> Self {
+    pub fn new(config: RecorderConfig) -> Self {
         Self {
-            storage_path,
-            link_path,
+            storage_path: config.record_storage_path(),
+            link_path: config.record_link_path(),
+            audible_threshold_db: config.audible_threshold_db(),
+            audible_min_ratio: config.audible_min_ratio(),
+            format: config.record_format(),
+            mono: config.record_mono(),
+            sample_rate: config.record_sample_rate(),
+            input_source: config.input_source(),
+            input_device: config.input_device(),
+            input_sample_rate: config.input_sample_rate(),
+            input_channels: config.input_channels(),
+            input_file: config.input_file(),
+            pre_roll_millis: config.pre_roll_millis(),
+            post_roll_millis: config.post_roll_millis(),
+            monitor: Arc::new(Mutex::new(None)),
         }
     }
 
-    #[allow(unreachable_code)]
-    pub fn start(&self, filename: String) -> anyhow::Result<(cpal::Stream, WavWriterHandle)> {
-        let device = match cpal::default_host().default_input_device() {
-            Some(device) => device,
-            None => return anyhow::bail!("No default input device found."),
-        };
+    /// 录音文件名，扩展名由录音格式决定
+    pub fn file_name(&self, id: &str) -> String {
+        format!("{}.{}", id, self.format.extension())
+    }
 
-        let config = device
-            .default_input_config()
-            .inspect_err(|e| error!("No default input config found: {e}"))?;
+    /// 写入录音元数据文件，并追加到录音索引
+    pub fn write_meta(&self, meta: &RecordMeta) -> anyhow::Result<()> {
+        meta::write(&self.storage_path, meta)
+    }
 
-        let path = format!("{}/{}", self.storage_path, filename);
-        let spec = Self::wav_format_from_config(&config);
-        let writer = hound::WavWriter::create(path.clone(), spec)?;
-        let writer = Arc::new(Mutex::new(Some(writer)));
+    /// 按鸡舍、时间范围查询录音索引
+    pub fn query_records(&self, query: &RecordQuery) -> anyhow::Result<Vec<RecordMeta>> {
+        meta::query(&self.storage_path, query)
+    }
 
-        let writer_clone = writer.clone();
-        let err_fn = move |e| {
-            error!("Stream build failed: {e}");
-        };
+    pub fn storage_path(&self) -> &str {
+        &self.storage_path
+    }
 
-        info!("config.sample_format: {:?}", config.sample_format());
+    /// 启动常开的预录输入，未配置预录时不做处理
+    pub fn start_monitor(&self) -> anyhow::Result<()> {
+        if self.pre_roll_millis == 0 {
+            return Ok(());
+        }
 
-        let stream = match config.sample_format() {
-            cpal::SampleFormat::I8 => device.build_input_stream(
-                &config.into(),
-                move |data, _: &_| Self::write_input_data::<i8, i8>(data, &writer_clone),
-                err_fn,
-                None,
-            )?,
-            cpal::SampleFormat::I16 => device.build_input_stream(
-                &config.into(),
-                move |data, _: &_| Self::write_input_data::<i16, i16>(data, &writer_clone),
-                err_fn,
-                None,
-            )?,
-            cpal::SampleFormat::I32 => device.build_input_stream(
-                &config.into(),
-                move |data, _: &_| Self::write_input_data::<i32, i32>(data, &writer_clone),
-                err_fn,
-                None,
-            )?,
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &config.into(),
-                move |data, _: &_| Self::write_input_data::<f32, f32>(data, &writer_clone),
-                err_fn,
-                None,
-            )?,
-            sample_format => {
-                return anyhow::bail!("Unsupported sample format: {sample_format}");
+        let mut monitor = self
+            .monitor
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock monitor failed: {e}"))?;
+        if monitor.is_some() {
+            return Ok(());
+        }
+
+        // 环形缓冲需要输入规格，先占位，拿到规格后再创建
+        let state = Arc::new(Mutex::new(MonitorState {
+            ring: RingBuffer::new(0, 0, 0),
+            captures: Vec::new(),
+            staging: HashMap::new(),
+            next_staging_id: 0,
+        }));
+        let state_clone = state.clone();
+        let (stream, sample_rate, channels) = self.open_input(move |data| {
+            if let Ok(mut state) = state_clone.lock() {
+                state.feed(data);
             }
+        })?;
+        {
+            let mut state = state
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Lock monitor state failed: {e}"))?;
+            state.ring = RingBuffer::new(self.pre_roll_millis, sample_rate, channels);
+        }
+        Self::play_stream(&stream)?;
+
+        info!(
+            "Record monitor started, pre_roll: {}ms, sample_rate: {sample_rate}, channels: {channels}",
+            self.pre_roll_millis
+        );
+        *monitor = Some(Monitor {
+            _stream: stream,
+            sample_rate,
+            channels,
+            state,
+        });
+
+        Ok(())
+    }
+
+    pub fn start(&self, filename: String) -> anyhow::Result<(InputStream, CaptureHandle)> {
+        let path = format!("{}/{}", self.storage_path, filename);
+        let (stream, writer) = if self.pre_roll_millis > 0 {
+            self.start_monitor()?;
+            (InputStream::Monitor, self.attach(&path)?)
+        } else {
+            let writer: CaptureHandle = Arc::new(Mutex::new(None));
+            let writer_clone = writer.clone();
+            let (stream, sample_rate, channels) = self.open_input(move |data| {
+                if let Ok(mut guard) = writer_clone.try_lock()
+                    && let Some(capture) = guard.as_mut()
+                {
+                    capture.write(data);
+                }
+            })?;
+            let capture = self.create_capture(&path, sample_rate, channels)?;
+            writer
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Lock writer failed: {e}"))?
+                .replace(capture);
+            (stream, writer)
         };
 
         let link_path = format!("{}/sl_{}", self.link_path, filename);
         fs::symlink(path, link_path.clone())
             .inspect_err(|e| error!("Failed for creating link path:{}, error: {e}", link_path))?;
 
-        stream
-            .play()
-            .inspect_err(|e| error!("Record failed: {e}"))?;
+        Self::play_stream(&stream)?;
 
         Ok((stream, writer))
     }
 
-    fn wav_format_from_config(config: &cpal::SupportedStreamConfig) -> hound::WavSpec {
-        hound::WavSpec {
-            channels: config.channels() as _,
-            sample_rate: config.sample_rate().0 as _,
-            bits_per_sample: (config.sample_format().sample_size() * 8) as _,
-            sample_format: Self::sample_format(config.sample_format()),
+    fn play_stream(stream: &InputStream) -> anyhow::Result<()> {
+        if let InputStream::Device(stream) = stream {
+            stream
+                .play()
+                .inspect_err(|e| error!("Record failed: {e}"))?;
         }
+
+        Ok(())
     }
 
-    fn sample_format(format: cpal::SampleFormat) -> hound::SampleFormat {
-        if format.is_float() {
-            hound::SampleFormat::Float
-        } else {
-            hound::SampleFormat::Int
+    /// 挂接到常开输入，先写入预录缓冲的音频
+    fn attach(&self, path: &str) -> anyhow::Result<CaptureHandle> {
+        let monitor = self
+            .monitor
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock monitor failed: {e}"))?;
+        let monitor = monitor
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Record monitor not started!"))?;
+
+        let mut capture = self.create_capture(path, monitor.sample_rate, monitor.channels)?;
+        let lock_state = || {
+            monitor
+                .state
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Lock monitor state failed: {e}"))
+        };
+
+        // 仅在锁内复制预录缓冲，编码写文件在锁外进行，避免阻塞实时输入回调
+        let (pre_roll, staging_id) = {
+            let mut state = lock_state()?;
+            let id = state.next_staging_id;
+            state.next_staging_id += 1;
+            state.staging.insert(id, Vec::new());
+            debug!("Write {} pre-roll samples...", state.ring.len());
+            (state.ring.iter().collect::<Vec<_>>(), id)
+        };
+        capture.write_pre_roll(pre_roll.into_iter());
+
+        let mut state = lock_state()?;
+        let staged = state.staging.remove(&staging_id).unwrap_or_default();
+        capture.write(&staged);
+        let writer = Arc::new(Mutex::new(Some(capture)));
+        state.captures.push(writer.clone());
+
+        Ok(writer)
+    }
+
+    fn detach(&self, writer: &CaptureHandle) {
+        if let Ok(monitor) = self.monitor.lock()
+            && let Some(monitor) = monitor.as_ref()
+            && let Ok(mut state) = monitor.state.lock()
+        {
+            state.captures.retain(|c| !Arc::ptr_eq(c, writer));
+        }
+    }
+
+    fn create_capture(
+        &self,
+        path: &str,
+        in_rate: u32,
+        in_channels: u16,
+    ) -> anyhow::Result<Capture> {
+        let out_rate = self.sample_rate.unwrap_or(in_rate);
+        let converter = Converter::new(in_channels, in_rate, self.mono, out_rate)?;
+        let writer = RecordWriter::create(&self.format, path, out_rate, converter.out_channels())?;
+        let meter = LevelMeter::new(
+            in_rate,
+            in_channels,
+            self.audible_threshold_db,
+            self.audible_min_ratio,
+        );
+
+        Ok(Capture {
+            writer,
+            converter,
+            meter,
+        })
+    }
+
+    /// 打开录音输入，返回 (输入流, 采样率, 声道数)
+    fn open_input<F>(&self, on_data: F) -> anyhow::Result<(InputStream, u32, u16)>
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        match self.input_source {
+            InputSource::Device => self.open_device(on_data),
+            InputSource::File => self.open_file(on_data),
+        }
+    }
+
+    /// 检查录音输入是否可用，返回输入说明，不打开输入流
+    pub fn check_input(&self) -> anyhow::Result<String> {
+        match self.input_source {
+            InputSource::Device => {
+                let device = self.input_device()?;
+                let config = self.input_config(&device)?;
+                Ok(format!(
+                    "{}, {} Hz, {} channels",
+                    device.name().unwrap_or_default(),
+                    config.sample_rate().0,
+                    config.channels()
+                ))
+            }
+            InputSource::File => {
+                let input_file = match self.input_file.clone() {
+                    Some(input_file) => input_file,
+                    None => anyhow::bail!("Input source is file, but input_file not configured."),
+                };
+                let (rate, channels) = FileStream::spec(&input_file)?;
+                Ok(format!("{input_file}, {rate} Hz, {channels} channels"))
+            }
         }
     }
 
-    fn write_input_data<T, U>(input: &[T], writer: &WavWriterHandle)
+    fn open_file<F>(&self, on_data: F) -> anyhow::Result<(InputStream, u32, u16)>
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        let input_file = match self.input_file.clone() {
+            Some(input_file) => input_file,
+            None => anyhow::bail!("Input source is file, but input_file not configured."),
+        };
+
+        let (in_rate, in_channels) = FileStream::spec(&input_file)?;
+        let stream = FileStream::start(input_file, on_data)?;
+
+        Ok((InputStream::File(stream), in_rate, in_channels))
+    }
+
+    fn open_device<F>(&self, on_data: F) -> anyhow::Result<(InputStream, u32, u16)>
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        let device = self.input_device()?;
+        let config = self.input_config(&device)?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels();
+
+        info!(
+            "Record input config, sample_format: {:?}, sample_rate: {}, channels: {}",
+            config.sample_format(),
+            sample_rate,
+            channels
+        );
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::I8 => Self::build_stream::<i8, F>(&device, config, on_data)?,
+            cpal::SampleFormat::I16 => Self::build_stream::<i16, F>(&device, config, on_data)?,
+            cpal::SampleFormat::I32 => Self::build_stream::<i32, F>(&device, config, on_data)?,
+            cpal::SampleFormat::F32 => Self::build_stream::<f32, F>(&device, config, on_data)?,
+            sample_format => {
+                anyhow::bail!("Unsupported sample format: {sample_format}");
+            }
+        };
+
+        Ok((InputStream::Device(stream), sample_rate, channels))
+    }
+
+    fn build_stream<T, F>(
+        device: &cpal::Device,
+        config: cpal::SupportedStreamConfig,
+        mut on_data: F,
+    ) -> anyhow::Result<cpal::Stream>
     where
-        T: Sample,
-        U: Sample + hound::Sample + FromSample<T>,
+        T: SizedSample,
+        f32: FromSample<T>,
+        F: FnMut(&[f32]) + Send + 'static,
     {
-        if let Ok(mut guard) = writer.try_lock() {
-            if let Some(writer) = guard.as_mut() {
-                for &sample in input.iter() {
-                    let sample: U = U::from_sample(sample);
-                    writer.write_sample(sample).ok();
+        let err_fn = move |e| {
+            error!("Stream build failed: {e}");
+        };
+
+        let mut buffer = Vec::new();
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[T], _: &_| {
+                buffer.clear();
+                buffer.extend(data.iter().map(|&s| f32::from_sample(s)));
+                on_data(&buffer);
+            },
+            err_fn,
+            None,
+        )?;
+
+        Ok(stream)
+    }
+
+    /// 按名称选择输入设备，未配置或未找到时使用默认输入设备
+    fn input_device(&self) -> anyhow::Result<cpal::Device> {
+        let host = cpal::default_host();
+        if let Some(name) = self.input_device.as_ref() {
+            match host.input_devices() {
+                Ok(devices) => {
+                    for device in devices {
+                        if device.name().is_ok_and(|n| &n == name) {
+                            return Ok(device);
+                        }
+                    }
+                    warn!("Input device: {name} not found, use the default input device.");
                 }
+                Err(e) => error!("Failed for listing input devices: {e}"),
+            }
+        }
+
+        match host.default_input_device() {
+            Some(device) => Ok(device),
+            None => anyhow::bail!("No default input device found."),
+        }
+    }
+
+    /// 按配置的采样率、声道数选择输入配置，无匹配时使用设备默认配置
+    fn input_config(&self, device: &cpal::Device) -> anyhow::Result<cpal::SupportedStreamConfig> {
+        let default_config = device
+            .default_input_config()
+            .inspect_err(|e| error!("No default input config found: {e}"))?;
+
+        if self.input_sample_rate.is_none() && self.input_channels.is_none() {
+            return Ok(default_config);
+        }
+
+        let sample_rate = cpal::SampleRate(
+            self.input_sample_rate
+                .unwrap_or(default_config.sample_rate().0),
+        );
+        let channels = self.input_channels.unwrap_or(default_config.channels());
+        for range in device.supported_input_configs()? {
+            if range.channels() != channels
+                || range.sample_format() != default_config.sample_format()
+            {
+                continue;
+            }
+            if let Some(config) = range.try_with_sample_rate(sample_rate) {
+                return Ok(config);
             }
         }
+
+        warn!(
+            "Input config, sample_rate: {}, channels: {} not supported, use the default config.",
+            sample_rate.0, channels
+        );
+        Ok(default_config)
     }
 
-    pub fn stop(&self, stream: cpal::Stream, writer: WavWriterHandle) -> anyhow::Result<()> {
+    /// 停止录音，返回录音期间的电平统计
+    ///
+    /// 配置了停止后录制时长时继续录制，到时后关闭录音文件再返回，之后写入的元数据及播放记录指向完整的文件
+    pub async fn stop(
+        &self,
+        stream: InputStream,
+        writer: CaptureHandle,
+    ) -> anyhow::Result<RecordLevel> {
+        let level = {
+            let writer = writer
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Lock writer failed: {e}"))?;
+            writer
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Writer is None!"))?
+                .meter
+                .level()
+        };
+
+        if self.post_roll_millis > 0 {
+            debug!(
+                "Keep recording for {}ms post-roll...",
+                self.post_roll_millis
+            );
+            tokio::time::sleep(Duration::from_millis(self.post_roll_millis)).await;
+        }
+        let recorder = self.clone();
+        tokio::task::spawn_blocking(move || recorder.finish(stream, writer))
+            .await
+            .map_err(|e| anyhow::anyhow!("Finish record failed: {e}"))??;
+
+        debug!("Recorder stopped, level: {level}");
+        Ok(level)
+    }
+
+    fn finish(&self, stream: InputStream, writer: CaptureHandle) -> anyhow::Result<()> {
         debug!("Drop stream...");
+        if let InputStream::Monitor = stream {
+            self.detach(&writer);
+        }
         drop(stream);
         let mut writer = writer
             .lock()
             .map_err(|e| anyhow::anyhow!("Lock writer failed: {e}"))?;
         debug!("Got writer lock, try to finalize it...");
-        let writer = writer
+        let capture = writer
             .take()
             .ok_or_else(|| anyhow::anyhow!("Writer is None!"))?;
-        writer
+        capture
             .finalize()
             .map_err(|e| anyhow::anyhow!("Writer finalize failed: {e}"))?;
 
-        debug!("Recorder stopped");
         Ok(())
     }
 }
@@ -146,7 +622,7 @@ impl Recorder {
 mod recorder_tests {
     use tracing::info;
 
-    use crate::recorder::Recorder;
+    use crate::{config::RecorderConfig, recorder::Recorder};
     use std::time::Duration;
 
     #[tokio::test]
@@ -154,7 +630,9 @@ mod recorder_tests {
         // 确保 /tmp 目录存在
         std::fs::create_dir_all("/tmp").unwrap();
 
-        let recorder = Recorder::new("/tmp".to_string(), "/tmp".to_string());
+        let config: RecorderConfig =
+            toml::from_str("record_storage_path = \"/tmp\"\nrecord_link_path = \"/tmp\"").unwrap();
+        let recorder = Recorder::new(config);
 
         // 开始录制
         let (stream, writer) = recorder.start("test.wav".to_string()).unwrap();
@@ -164,7 +642,99 @@ mod recorder_tests {
 
         // 停止录制
         info!("Stopping recording...");
-        recorder.stop(stream, writer).unwrap();
-        info!("Recording stopped and file saved.");
+        let level = recorder.stop(stream, writer).await.unwrap();
+        info!("Recording stopped and file saved, level: {level}");
+    }
+
+    #[tokio::test]
+    async fn file_record_test() {
+        let dir = std::env::temp_dir().join("alarm_player_file_record");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input_file = dir.join("input.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&input_file, spec).unwrap();
+        for i in 0..16000 {
+            let t = i as f32 / 16000.0;
+            let sample = (8000.0 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()) as i16;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config: RecorderConfig = toml::from_str(&format!(
+            "record_storage_path = \"{dir}\"\nrecord_link_path = \"{dir}\"\n\
+             record_format = \"flac\"\nrecord_mono = true\nrecord_sample_rate = 8000\n\
+             input_source = \"file\"\ninput_file = \"{file}\"",
+            dir = dir.display(),
+            file = input_file.display()
+        ))
+        .unwrap();
+        let recorder = Recorder::new(config);
+        let filename = recorder.file_name("file_record");
+        let _ = std::fs::remove_file(dir.join(&filename));
+        let _ = std::fs::remove_file(dir.join(format!("sl_{filename}")));
+
+        let (stream, writer) = recorder.start(filename.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let level = recorder.stop(stream, writer).await.unwrap();
+        info!("File record level: {level}");
+
+        assert!(level.audible);
+        let reader =
+            rodio::Decoder::try_from(std::fs::File::open(dir.join(&filename)).unwrap()).unwrap();
+        assert_eq!(rodio::Source::channels(&reader), 1);
+        assert_eq!(rodio::Source::sample_rate(&reader), 8000);
+        assert!(reader.count() > 0);
+    }
+
+    #[tokio::test]
+    async fn pre_roll_record_test() {
+        let dir = std::env::temp_dir().join("alarm_player_pre_roll_record");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input_file = dir.join("input.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&input_file, spec).unwrap();
+        for i in 0..8000 {
+            let t = i as f32 / 8000.0;
+            let sample = (8000.0 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()) as i16;
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config: RecorderConfig = toml::from_str(&format!(
+            "record_storage_path = \"{dir}\"\nrecord_link_path = \"{dir}\"\n\
+             input_source = \"file\"\ninput_file = \"{file}\"\n\
+             pre_roll_millis = 400\npost_roll_millis = 300",
+            dir = dir.display(),
+            file = input_file.display()
+        ))
+        .unwrap();
+        let recorder = Recorder::new(config);
+        recorder.start_monitor().unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+
+        let filename = recorder.file_name("pre_roll_record");
+        let _ = std::fs::remove_file(dir.join(&filename));
+        let _ = std::fs::remove_file(dir.join(format!("sl_{filename}")));
+        let (stream, writer) = recorder.start(filename.clone()).unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let level = recorder.stop(stream, writer).await.unwrap();
+        assert!(level.audible);
+
+        // 停止后录制结束、文件关闭后才返回
+        let reader = hound::WavReader::open(dir.join(&filename)).unwrap();
+        // 预录 400ms + 录音 200ms + 停止后录制 300ms
+        let millis = reader.duration() as u64 * 1000 / 8000;
+        assert!(millis >= 800, "record duration: {millis}ms");
     }
 }
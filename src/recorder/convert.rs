@@ -0,0 +1,214 @@
+use rubato::{FftFixedIn, Resampler};
+
+/// 重采样每次处理的输入帧数
+const CHUNK_FRAMES: usize = 1024;
+
+/// 录音格式转换，支持混缩为单声道及重采样
+///
+/// 采样率不同时使用带抗混叠滤波的 FFT 重采样，输入按块缓存，结束时调用 finish 输出剩余部分
+pub struct Converter {
+    in_channels: usize,
+    out_channels: usize,
+    in_rate: u32,
+    out_rate: u32,
+    frame: Vec<f32>,
+    // 待重采样的输入，按声道分开
+    input: Vec<Vec<f32>>,
+    resampler: Option<FftFixedIn<f32>>,
+    // 重采样引入的延迟，输出开头丢弃的帧数
+    delay: usize,
+    frames_in: u64,
+    frames_out: u64,
+}
+
+impl Converter {
+    pub fn new(in_channels: u16, in_rate: u32, mono: bool, out_rate: u32) -> anyhow::Result<Self> {
+        let in_channels = in_channels.max(1) as usize;
+        let out_channels = if mono { 1 } else { in_channels };
+        let resampler = if in_rate == out_rate {
+            None
+        } else {
+            let resampler = FftFixedIn::new(
+                in_rate as usize,
+                out_rate as usize,
+                CHUNK_FRAMES,
+                2,
+                out_channels,
+            )
+            .map_err(|e| anyhow::anyhow!("Create resampler {in_rate} -> {out_rate} failed: {e}"))?;
+            Some(resampler)
+        };
+        let delay = resampler.as_ref().map_or(0, |r| r.output_delay());
+
+        Ok(Self {
+            in_channels,
+            out_channels,
+            in_rate,
+            out_rate,
+            frame: Vec::with_capacity(in_channels),
+            input: vec![Vec::with_capacity(CHUNK_FRAMES); out_channels],
+            resampler,
+            delay,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    pub fn out_channels(&self) -> u16 {
+        self.out_channels as u16
+    }
+
+    /// 输入一个交错排列的采样，输出转换后的交错采样
+    pub fn push(&mut self, sample: f32, mut out: impl FnMut(f32)) {
+        self.frame.push(sample);
+        if self.frame.len() < self.in_channels {
+            return;
+        }
+
+        if self.out_channels == 1 {
+            let mixed = self.frame.iter().sum::<f32>() / self.in_channels as f32;
+            self.input[0].push(mixed);
+        } else {
+            for (input, &s) in self.input.iter_mut().zip(self.frame.iter()) {
+                input.push(s);
+            }
+        }
+        self.frame.clear();
+        self.frames_in += 1;
+
+        let Some(resampler) = self.resampler.as_mut() else {
+            self.drain(&mut out);
+            return;
+        };
+        if self.input[0].len() < resampler.input_frames_next() {
+            return;
+        }
+        match resampler.process(&self.input, None) {
+            Ok(output) => self.emit(&output, &mut out),
+            Err(e) => tracing::error!("Resample failed: {e}"),
+        }
+        self.input.iter_mut().for_each(Vec::clear);
+    }
+
+    /// 输出缓存及重采样延迟中剩余的采样，总帧数与输入时长一致
+    pub fn finish(&mut self, mut out: impl FnMut(f32)) {
+        let Some(mut resampler) = self.resampler.take() else {
+            return;
+        };
+        let expected = self.frames_in * self.out_rate as u64 / self.in_rate as u64;
+        let mut pending = Some(std::mem::replace(
+            &mut self.input,
+            vec![Vec::new(); self.out_channels],
+        ));
+        while self.frames_out < expected {
+            let result = match pending.take() {
+                Some(input) => resampler.process_partial(Some(&input), None),
+                None => resampler.process_partial(None::<&[Vec<f32>]>, None),
+            };
+            match result {
+                Ok(output) if !output[0].is_empty() => {
+                    let remaining = (expected - self.frames_out) as usize + self.delay;
+                    let output: Vec<Vec<f32>> = output
+                        .into_iter()
+                        .map(|mut ch| {
+                            ch.truncate(remaining);
+                            ch
+                        })
+                        .collect();
+                    self.emit(&output, &mut out);
+                }
+                Ok(_) => break,
+                Err(e) => {
+                    tracing::error!("Resample failed: {e}");
+                    break;
+                }
+            }
+        }
+        self.resampler = Some(resampler);
+    }
+
+    /// 不重采样时直接输出
+    fn drain(&mut self, out: &mut impl FnMut(f32)) {
+        for i in 0..self.input[0].len() {
+            for input in &self.input {
+                out(input[i]);
+            }
+        }
+        self.frames_out += self.input[0].len() as u64;
+        self.input.iter_mut().for_each(Vec::clear);
+    }
+
+    /// 交错输出重采样结果，跳过开头的延迟
+    fn emit(&mut self, output: &[Vec<f32>], out: &mut impl FnMut(f32)) {
+        let skip = self.delay.min(output[0].len());
+        self.delay -= skip;
+        for i in skip..output[0].len() {
+            for ch in output {
+                out(ch[i]);
+            }
+        }
+        self.frames_out += (output[0].len() - skip) as u64;
+    }
+}
+
+#[cfg(test)]
+mod convert_tests {
+    use super::Converter;
+
+    fn convert(converter: &mut Converter, input: &[f32]) -> Vec<f32> {
+        let mut output = Vec::new();
+        for &s in input {
+            converter.push(s, |s| output.push(s));
+        }
+        converter.finish(|s| output.push(s));
+        output
+    }
+
+    fn tone(rate: u32, freq: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_downmix() {
+        let mut converter = Converter::new(2, 48000, true, 48000).unwrap();
+        let output = convert(&mut converter, &[0.2, 0.4, -1.0, 0.0]);
+        assert_eq!(output, vec![0.3, -0.5]);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let mut converter = Converter::new(1, 48000, false, 16000).unwrap();
+        let output = convert(&mut converter, &tone(48000, 440.0, 48000));
+        assert_eq!(output.len(), 16000);
+        // 去除首尾的滤波过渡后，通带内的幅度保持不变
+        let expected = rms(&tone(16000, 440.0, 16000)[1000..15000]);
+        assert!((rms(&output[1000..15000]) - expected).abs() < 0.005);
+
+        // 高于目标奈奎斯特频率的分量被滤除，不产生混叠
+        let mut converter = Converter::new(1, 48000, false, 16000).unwrap();
+        let output = convert(&mut converter, &tone(48000, 10000.0, 48000));
+        assert!(rms(&output[1000..15000]) < 0.01);
+    }
+
+    #[test]
+    fn test_upsample() {
+        let mut converter = Converter::new(2, 8000, false, 16000).unwrap();
+        let input: Vec<f32> = tone(8000, 440.0, 8000)
+            .into_iter()
+            .flat_map(|s| [s, -s])
+            .collect();
+        let output = convert(&mut converter, &input);
+        assert_eq!(output.len(), 2 * 16000);
+        let expected = tone(16000, 440.0, 16000);
+        for i in 1000..15000 {
+            assert!((output[2 * i] - expected[i]).abs() < 0.01);
+            assert!((output[2 * i + 1] + expected[i]).abs() < 0.01);
+        }
+    }
+}
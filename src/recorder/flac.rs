@@ -0,0 +1,427 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+// 每帧采样数
+const BLOCK_SIZE: usize = 4096;
+// 位深，录音统一为 16 bit
+const BITS_PER_SAMPLE: u32 = 16;
+// STREAMINFO 元数据块长度
+const STREAMINFO_LEN: u32 = 34;
+// Rice 编码参数上限，15 为转义码
+const MAX_RICE_PARAM: u32 = 14;
+
+/// 16 bit FLAC 编码写入器
+///
+/// 仅实现固定线性预测 + Rice 残差编码，各声道独立编码，满足录音存档需求
+pub struct FlacWriter {
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    channels: usize,
+    // 当前帧缓存，按声道分开
+    block: Vec<Vec<i32>>,
+    // 交错采样中下一个采样所属声道
+    channel: usize,
+    frame_number: u64,
+    total_samples: u64,
+    min_frame_size: u32,
+    max_frame_size: u32,
+}
+
+impl FlacWriter {
+    pub fn create(path: &str, sample_rate: u32, channels: u16) -> anyhow::Result<Self> {
+        if channels == 0 || channels > 8 {
+            anyhow::bail!("Unsupported flac channels: {channels}");
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(b"fLaC")?;
+        let mut flac = Self {
+            writer,
+            sample_rate,
+            channels: channels as usize,
+            block: vec![Vec::with_capacity(BLOCK_SIZE); channels as usize],
+            channel: 0,
+            frame_number: 0,
+            total_samples: 0,
+            min_frame_size: u32::MAX,
+            max_frame_size: 0,
+        };
+        // 先写入占位的 STREAMINFO，结束时回填
+        flac.write_streaminfo()?;
+
+        Ok(flac)
+    }
+
+    /// 写入一个交错排列的采样
+    pub fn write_sample(&mut self, sample: i16) -> anyhow::Result<()> {
+        self.block[self.channel].push(sample as i32);
+        self.channel += 1;
+        if self.channel == self.channels {
+            self.channel = 0;
+            if self.block[0].len() == BLOCK_SIZE {
+                self.write_frame()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finalize(mut self) -> anyhow::Result<()> {
+        // 丢弃不完整的最后一帧采样
+        let len = self.block[self.channels - 1].len();
+        for samples in self.block.iter_mut() {
+            samples.truncate(len);
+        }
+        if len > 0 {
+            self.write_frame()?;
+        }
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.write_streaminfo()?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    fn write_streaminfo(&mut self) -> anyhow::Result<()> {
+        let mut bw = BitWriter::default();
+        // 最后一个元数据块，类型 0: STREAMINFO
+        bw.write(1, 1);
+        bw.write(0, 7);
+        bw.write(STREAMINFO_LEN as u64, 24);
+        bw.write(BLOCK_SIZE as u64, 16);
+        bw.write(BLOCK_SIZE as u64, 16);
+        let (min_frame_size, max_frame_size) = if self.max_frame_size > 0 {
+            (self.min_frame_size, self.max_frame_size)
+        } else {
+            (0, 0)
+        };
+        bw.write(min_frame_size as u64, 24);
+        bw.write(max_frame_size as u64, 24);
+        bw.write(self.sample_rate as u64, 20);
+        bw.write((self.channels - 1) as u64, 3);
+        bw.write((BITS_PER_SAMPLE - 1) as u64, 5);
+        bw.write(self.total_samples, 36);
+        // MD5 未计算，全 0 表示未知
+        bw.write(0, 64);
+        bw.write(0, 64);
+        self.writer.write_all(&bw.into_bytes())?;
+
+        Ok(())
+    }
+
+    fn write_frame(&mut self) -> anyhow::Result<()> {
+        let block_size = self.block[0].len();
+        let mut bw = BitWriter::default();
+
+        // 帧头: 同步码 + 固定块大小策略
+        bw.write(0b11_1111_1111_1110, 14);
+        bw.write(0, 1);
+        bw.write(0, 1);
+        // 块大小取帧头末尾的 16 bit 值，采样率取 STREAMINFO
+        bw.write(0b0111, 4);
+        bw.write(0b0000, 4);
+        // 独立声道，16 bit
+        bw.write((self.channels - 1) as u64, 4);
+        bw.write(0b100, 3);
+        bw.write(0, 1);
+        bw.write_utf8(self.frame_number);
+        bw.write((block_size - 1) as u64, 16);
+        let crc = crc8(bw.bytes());
+        bw.write(crc as u64, 8);
+
+        for samples in &self.block {
+            Self::write_subframe(&mut bw, samples);
+        }
+
+        bw.align();
+        let crc = crc16(bw.bytes());
+        bw.write(crc as u64, 16);
+
+        let bytes = bw.into_bytes();
+        self.writer.write_all(&bytes)?;
+
+        self.min_frame_size = self.min_frame_size.min(bytes.len() as u32);
+        self.max_frame_size = self.max_frame_size.max(bytes.len() as u32);
+        self.total_samples += block_size as u64;
+        self.frame_number += 1;
+        for samples in self.block.iter_mut() {
+            samples.clear();
+        }
+
+        Ok(())
+    }
+
+    fn write_subframe(bw: &mut BitWriter, samples: &[i32]) {
+        if samples.iter().all(|&s| s == samples[0]) {
+            // CONSTANT
+            bw.write(0b0000_0000, 8);
+            bw.write_signed(samples[0] as i64, BITS_PER_SAMPLE);
+            return;
+        }
+
+        let verbatim_bits = samples.len() as u64 * BITS_PER_SAMPLE as u64;
+        let mut best: Option<(usize, u32, u64, Vec<i32>)> = None;
+        for order in 0..=4.min(samples.len() - 1) {
+            let residuals = Self::fixed_residuals(samples, order);
+            let (param, bits) = Self::rice_param(&residuals);
+            let bits = bits + (order as u64) * BITS_PER_SAMPLE as u64;
+            if best.as_ref().is_none_or(|b| bits < b.2) {
+                best = Some((order, param, bits, residuals));
+            }
+        }
+
+        match best {
+            Some((order, param, bits, residuals)) if bits < verbatim_bits => {
+                // FIXED
+                bw.write(0b0001_0000 | (order as u64) << 1, 8);
+                for &s in &samples[..order] {
+                    bw.write_signed(s as i64, BITS_PER_SAMPLE);
+                }
+                // 4 bit Rice 参数，分区阶数 0
+                bw.write(0b00, 2);
+                bw.write(0, 4);
+                bw.write(param as u64, 4);
+                for r in residuals {
+                    bw.write_rice(r, param);
+                }
+            }
+            _ => {
+                // VERBATIM
+                bw.write(0b0000_0010, 8);
+                for &s in samples {
+                    bw.write_signed(s as i64, BITS_PER_SAMPLE);
+                }
+            }
+        }
+    }
+
+    fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+        (order..samples.len())
+            .map(|i| match order {
+                0 => samples[i],
+                1 => samples[i] - samples[i - 1],
+                2 => samples[i] - 2 * samples[i - 1] + samples[i - 2],
+                3 => samples[i] - 3 * samples[i - 1] + 3 * samples[i - 2] - samples[i - 3],
+                _ => {
+                    samples[i] - 4 * samples[i - 1] + 6 * samples[i - 2] - 4 * samples[i - 3]
+                        + samples[i - 4]
+                }
+            })
+            .collect()
+    }
+
+    /// 选取 Rice 参数，返回参数及编码所需位数
+    fn rice_param(residuals: &[i32]) -> (u32, u64) {
+        let bits = |param: u32| -> u64 {
+            residuals
+                .iter()
+                .map(|&r| (zigzag(r) >> param) + 1 + param as u64)
+                .sum()
+        };
+
+        let n = residuals.len().max(1) as u64;
+        let mean = residuals.iter().map(|&r| zigzag(r)).sum::<u64>() / n;
+        let estimate = (64 - mean.leading_zeros()).min(MAX_RICE_PARAM);
+
+        let mut best = (estimate, bits(estimate));
+        for param in [
+            estimate.saturating_sub(1),
+            (estimate + 1).min(MAX_RICE_PARAM),
+        ] {
+            let b = bits(param);
+            if b < best.1 {
+                best = (param, b);
+            }
+        }
+
+        best
+    }
+}
+
+fn zigzag(r: i32) -> u64 {
+    (((r as i64) << 1) ^ ((r as i64) >> 63)) as u64
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.acc = (self.acc << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.acc as u8);
+                self.acc = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64 & ((1u64 << bits) - 1), bits);
+    }
+
+    fn write_rice(&mut self, value: i32, param: u32) {
+        let u = zigzag(value);
+        let q = u >> param;
+        for _ in 0..q {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+        self.write(u, param);
+    }
+
+    /// 帧号采用类 UTF-8 编码
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+            return;
+        }
+
+        let mut extra = 1;
+        while extra < 6 && value >= 1u64 << (6 + 5 * extra) {
+            extra += 1;
+        }
+        let lead = (0xFF00u64 >> (extra + 1)) & 0xFF;
+        self.write(lead | (value >> (6 * extra)), 8);
+        for i in (0..extra).rev() {
+            self.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+        }
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    /// 已写满的字节
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &b in data {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod flac_tests {
+    use std::fs::File;
+
+    use rodio::{Decoder, Source};
+
+    use super::FlacWriter;
+
+    fn round_trip(name: &str, channels: u16, samples: &[i16]) {
+        let path = format!("{}/{name}", std::env::temp_dir().display());
+        let mut writer = FlacWriter::create(&path, 16000, channels).unwrap();
+        for &s in samples {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let decoder = Decoder::try_from(File::open(&path).unwrap()).unwrap();
+        assert_eq!(decoder.channels(), channels);
+        assert_eq!(decoder.sample_rate(), 16000);
+        let decoded: Vec<i16> = decoder.map(|s| (s * 32768.0).round() as i16).collect();
+        assert_eq!(decoded, samples);
+
+        // 使用独立的 claxon 解码器逐个采样比对
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(info.channels, channels as u32);
+        assert_eq!(info.sample_rate, 16000);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(
+            info.samples,
+            Some((samples.len() / channels as usize) as u64)
+        );
+        let mut count = 0;
+        for (i, s) in reader.samples().enumerate() {
+            assert_eq!(s.unwrap(), samples[i] as i32, "sample {i}");
+            count += 1;
+        }
+        assert_eq!(count, samples.len());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_tone() {
+        let samples: Vec<i16> = (0..10000)
+            .map(|i| {
+                let t = i as f32 / 16000.0;
+                (8000.0 * (2.0 * std::f32::consts::PI * 440.0 * t).sin()) as i16
+            })
+            .collect();
+        round_trip("flac_tone.flac", 1, &samples);
+    }
+
+    #[test]
+    fn test_round_trip_stereo_noise() {
+        let mut seed = 1u32;
+        let samples: Vec<i16> = (0..2 * 5000)
+            .map(|i| {
+                if i < 2 * 4096 {
+                    // 首帧静音，覆盖 CONSTANT 子帧
+                    return 0;
+                }
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as i16
+            })
+            .collect();
+        round_trip("flac_noise.flac", 2, &samples);
+    }
+
+    #[test]
+    fn test_round_trip_full_scale() {
+        // 满幅跳变，覆盖大残差及末尾不足一块的帧
+        let samples: Vec<i16> = (0..4096 + 100)
+            .map(|i| match i % 3 {
+                0 => i16::MAX,
+                1 => i16::MIN,
+                _ => 0,
+            })
+            .collect();
+        round_trip("flac_full_scale.flac", 1, &samples);
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+// 统计窗口时长，单位 ms
+const WINDOW_MILLIS: u32 = 100;
+// 静音电平下限，单位 dBFS
+const MIN_DB: f32 = -120.0;
+
+/// 录音电平分析结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordLevel {
+    /// 均方根电平，单位 dBFS
+    pub rms_db: f32,
+    /// 峰值电平，单位 dBFS
+    pub peak_db: f32,
+    /// 超过阈值的时长占比
+    pub active_ratio: f32,
+    /// 是否判定为可闻
+    pub audible: bool,
+}
+
+impl Display for RecordLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rms: {:.1}dBFS, peak: {:.1}dBFS, active: {:.1}%",
+            self.rms_db,
+            self.peak_db,
+            self.active_ratio * 100.0
+        )
+    }
+}
+
+/// 录音电平统计，按窗口计算超过阈值的时长占比
+pub struct LevelMeter {
+    threshold: f32,
+    min_active_ratio: f32,
+    window_len: usize,
+    sum_squares: f64,
+    count: u64,
+    peak: f32,
+    window_sum_squares: f64,
+    window_count: usize,
+    windows: u64,
+    active_windows: u64,
+}
+
+impl LevelMeter {
+    pub fn new(sample_rate: u32, channels: u16, threshold_db: f32, min_active_ratio: f32) -> Self {
+        let window_len = (sample_rate * WINDOW_MILLIS / 1000) as usize * channels.max(1) as usize;
+        Self {
+            threshold: Self::from_db(threshold_db),
+            min_active_ratio,
+            window_len: window_len.max(1),
+            sum_squares: 0.0,
+            count: 0,
+            peak: 0.0,
+            window_sum_squares: 0.0,
+            window_count: 0,
+            windows: 0,
+            active_windows: 0,
+        }
+    }
+
+    pub fn feed(&mut self, sample: f32) {
+        let square = (sample as f64) * (sample as f64);
+        self.sum_squares += square;
+        self.count += 1;
+        self.peak = self.peak.max(sample.abs());
+
+        self.window_sum_squares += square;
+        self.window_count += 1;
+        if self.window_count >= self.window_len {
+            let rms = (self.window_sum_squares / self.window_count as f64).sqrt() as f32;
+            self.windows += 1;
+            if rms >= self.threshold {
+                self.active_windows += 1;
+            }
+            self.window_sum_squares = 0.0;
+            self.window_count = 0;
+        }
+    }
+
+    pub fn level(&self) -> RecordLevel {
+        let rms = if self.count > 0 {
+            (self.sum_squares / self.count as f64).sqrt() as f32
+        } else {
+            0.0
+        };
+        let active_ratio = if self.windows > 0 {
+            self.active_windows as f32 / self.windows as f32
+        } else {
+            0.0
+        };
+
+        RecordLevel {
+            rms_db: Self::to_db(rms),
+            peak_db: Self::to_db(self.peak),
+            active_ratio,
+            audible: self.windows > 0 && active_ratio >= self.min_active_ratio,
+        }
+    }
+
+    fn to_db(amplitude: f32) -> f32 {
+        if amplitude <= 0.0 {
+            return MIN_DB;
+        }
+        (20.0 * amplitude.log10()).max(MIN_DB)
+    }
+
+    fn from_db(db: f32) -> f32 {
+        10f32.powf(db / 20.0)
+    }
+}
+
+#[cfg(test)]
+mod level_tests {
+    use super::LevelMeter;
+
+    #[test]
+    fn test_silence() {
+        let mut meter = LevelMeter::new(8000, 1, -40.0, 0.5);
+        for _ in 0..8000 {
+            meter.feed(0.0);
+        }
+        let level = meter.level();
+        assert!(!level.audible);
+        assert_eq!(level.active_ratio, 0.0);
+        assert_eq!(level.peak_db, -120.0);
+    }
+
+    #[test]
+    fn test_tone() {
+        let mut meter = LevelMeter::new(8000, 1, -40.0, 0.5);
+        for i in 0..8000 {
+            let t = i as f32 / 8000.0;
+            meter.feed(0.5 * (2.0 * std::f32::consts::PI * 440.0 * t).sin());
+        }
+        let level = meter.level();
+        assert!(level.audible);
+        assert!((level.peak_db - -6.0).abs() < 0.5);
+        assert!((level.rms_db - -9.0).abs() < 0.5);
+        assert_eq!(level.active_ratio, 1.0);
+    }
+
+    #[test]
+    fn test_partial_tone() {
+        let mut meter = LevelMeter::new(8000, 1, -40.0, 0.5);
+        for i in 0..8000 {
+            let sample = if i < 2400 { 0.5 } else { 0.0 };
+            meter.feed(sample);
+        }
+        let level = meter.level();
+        assert!(!level.audible);
+        assert!((level.active_ratio - 0.3).abs() < 1e-6);
+    }
+}
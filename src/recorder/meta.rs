@@ -0,0 +1,180 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::util::rfc3339_time;
+
+use super::RecordLevel;
+
+// 录音索引文件名，位于录音存储目录
+pub const INDEX_FILE: &str = "index.jsonl";
+
+/// 录音元数据，写入录音文件同目录的 JSON 文件并追加到录音索引
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordMeta {
+    /// 播放记录 id
+    pub id: String,
+    /// 录音文件名
+    pub record_file: String,
+    /// 报警标识: `{house_code}_{target_name}`
+    pub alarm_key: String,
+    pub house_code: String,
+    pub house_name: Option<String>,
+    /// 报警原始内容
+    pub content: String,
+    /// 播放内容，TTS 文本或音频地址
+    pub play_content: String,
+    pub is_test: bool,
+    /// 播放设备: `soundbox`/`soundpost:{device_id}`
+    pub devices: Vec<String>,
+    /// `音柱报警/音箱报警/音柱音箱`
+    pub play_type: Option<String>,
+    /// 播放结果
+    pub result: String,
+    pub has_error: bool,
+    pub err_message: Option<String>,
+    #[serde(with = "rfc3339_time")]
+    pub start_time: OffsetDateTime,
+    #[serde(with = "rfc3339_time")]
+    pub stop_time: OffsetDateTime,
+    /// 录音电平
+    pub level: Option<RecordLevel>,
+}
+
+/// 录音索引查询条件
+#[derive(Debug, Default, Clone)]
+pub struct RecordQuery {
+    /// 鸡舍码或鸡舍名称
+    pub house: Option<String>,
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+}
+
+impl RecordQuery {
+    fn matches(&self, meta: &RecordMeta) -> bool {
+        if let Some(house) = self.house.as_ref()
+            && &meta.house_code != house
+            && meta.house_name.as_ref() != Some(house)
+        {
+            return false;
+        }
+
+        if self.from.is_some_and(|from| meta.start_time < from) {
+            return false;
+        }
+
+        self.to.is_none_or(|to| meta.start_time <= to)
+    }
+}
+
+pub fn write(storage_path: &str, meta: &RecordMeta) -> anyhow::Result<()> {
+    let sidecar = format!("{}/{}.json", storage_path, meta.record_file);
+    let file = File::create(&sidecar)?;
+    serde_json::to_writer_pretty(file, meta)?;
+
+    let mut index = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(format!("{}/{}", storage_path, INDEX_FILE))?;
+    let mut line = serde_json::to_vec(meta)?;
+    line.push(b'\n');
+    index.write_all(&line)?;
+
+    Ok(())
+}
+
+pub fn query(storage_path: &str, query: &RecordQuery) -> anyhow::Result<Vec<RecordMeta>> {
+    let file = File::open(format!("{}/{}", storage_path, INDEX_FILE))?;
+    let mut result = Vec::new();
+    for (no, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<RecordMeta>(&line) {
+            Ok(meta) => {
+                if query.matches(&meta) {
+                    result.push(meta);
+                }
+            }
+            Err(e) => warn!("Invalid record index line: {}, err: {e}, skipped.", no + 1),
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod meta_tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::{RecordMeta, RecordQuery};
+
+    fn meta(id: &str, house_code: &str, start_time: OffsetDateTime) -> RecordMeta {
+        RecordMeta {
+            id: id.to_string(),
+            record_file: format!("{id}.wav"),
+            alarm_key: format!("{house_code}_高温报警"),
+            house_code: house_code.to_string(),
+            house_name: Some(format!("name_{house_code}")),
+            content: "温度 状态:报警".to_string(),
+            play_content: "[9200] 温度 报警".to_string(),
+            is_test: false,
+            devices: vec!["soundbox".to_string(), "soundpost:1".to_string()],
+            play_type: Some("音柱音箱".to_string()),
+            result: "Normal".to_string(),
+            has_error: false,
+            err_message: None,
+            start_time,
+            stop_time: start_time + Duration::seconds(30),
+            level: None,
+        }
+    }
+
+    #[test]
+    fn test_write_and_query() {
+        let dir = std::env::temp_dir().join("alarm_player_record_meta");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.display().to_string();
+
+        let now = OffsetDateTime::now_utc();
+        super::write(&path, &meta("a", "h1", now - Duration::hours(2))).unwrap();
+        super::write(&path, &meta("b", "h2", now - Duration::hours(1))).unwrap();
+        super::write(&path, &meta("c", "h1", now)).unwrap();
+        assert!(dir.join("a.wav.json").exists());
+
+        let all = super::query(&path, &RecordQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+
+        let query = RecordQuery {
+            house: Some("h1".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = super::query(&path, &query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        let query = RecordQuery {
+            house: Some("name_h1".to_string()),
+            from: Some(now - Duration::minutes(90)),
+            to: None,
+        };
+        let ids: Vec<String> = super::query(&path, &query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec!["c"]);
+    }
+}
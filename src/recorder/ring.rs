@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+/// 预录环形缓冲，保存最近一段交错排列的采样
+pub struct RingBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    /// 按时长计算容量，容量按声道数对齐，保证缓冲始终以完整帧开头
+    pub fn new(millis: u64, sample_rate: u32, channels: u16) -> Self {
+        let frames = (millis * sample_rate as u64 / 1000) as usize;
+        let capacity = frames * channels.max(1) as usize;
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, input: &[f32]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let input = &input[input.len().saturating_sub(self.capacity)..];
+        let overflow = (self.samples.len() + input.len()).saturating_sub(self.capacity);
+        self.samples.drain(..overflow);
+        self.samples.extend(input);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+#[cfg(test)]
+mod ring_tests {
+    use super::RingBuffer;
+
+    #[test]
+    fn test_keep_latest() {
+        // 10ms, 1000Hz, 双声道: 容量 20 个采样
+        let mut ring = RingBuffer::new(10, 1000, 2);
+        let input: Vec<f32> = (0..16).map(|i| i as f32).collect();
+        ring.push(&input);
+        assert_eq!(ring.len(), 16);
+
+        ring.push(&input[..8]);
+        assert_eq!(ring.len(), 20);
+        let expected: Vec<f32> = (4..16).chain(0..8).map(|i| i as f32).collect();
+        assert_eq!(ring.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_oversized_input() {
+        let mut ring = RingBuffer::new(10, 1000, 1);
+        let input: Vec<f32> = (0..25).map(|i| i as f32).collect();
+        ring.push(&input);
+        let expected: Vec<f32> = (15..25).map(|i| i as f32).collect();
+        assert_eq!(ring.iter().collect::<Vec<_>>(), expected);
+    }
+}
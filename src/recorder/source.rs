@@ -0,0 +1,119 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use tracing::{error, info};
+
+// 文件输入每次回调的时长，单位 ms
+const CHUNK_MILLIS: u32 = 20;
+
+/// 录音输入流，释放时停止录音输入
+pub enum InputStream {
+    Device(cpal::Stream),
+    File(FileStream),
+    // 挂接在常开的预录输入上，不单独持有输入流
+    Monitor,
+}
+
+/// 文件输入流，将 WAV 文件按实时速率循环回放给录音回调
+pub struct FileStream {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FileStream {
+    /// 读取 WAV 文件规格，返回 (采样率, 声道数)
+    pub fn spec(path: &str) -> anyhow::Result<(u32, u16)> {
+        let reader = hound::WavReader::open(path)
+            .map_err(|e| anyhow::anyhow!("Open input file: {path} failed: {e}"))?;
+        let spec = reader.spec();
+        Ok((spec.sample_rate, spec.channels))
+    }
+
+    pub fn start<F>(path: String, mut callback: F) -> anyhow::Result<Self>
+    where
+        F: FnMut(&[f32]) + Send + 'static,
+    {
+        let (sample_rate, channels) = Self::spec(&path)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("record-file-source".to_string())
+            .spawn(move || {
+                info!("Replay input file: {path} as record source...");
+                let chunk_len =
+                    (sample_rate * CHUNK_MILLIS / 1000) as usize * channels.max(1) as usize;
+                let chunk_duration = Duration::from_millis(CHUNK_MILLIS as u64);
+                let mut chunk = Vec::with_capacity(chunk_len);
+                let mut next = Instant::now();
+                while !stop_clone.load(Ordering::Relaxed) {
+                    let mut reader = match hound::WavReader::open(&path) {
+                        Ok(reader) => reader,
+                        Err(e) => {
+                            error!("Open input file: {path} failed: {e}");
+                            return;
+                        }
+                    };
+
+                    let spec = reader.spec();
+                    let samples: Box<dyn Iterator<Item = f32>> = match spec.sample_format {
+                        hound::SampleFormat::Float => {
+                            Box::new(reader.samples::<f32>().map(|s| s.unwrap_or_default()))
+                        }
+                        hound::SampleFormat::Int => {
+                            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                            Box::new(
+                                reader
+                                    .samples::<i32>()
+                                    .map(move |s| s.unwrap_or_default() as f32 / scale),
+                            )
+                        }
+                    };
+
+                    let mut empty = true;
+                    for sample in samples {
+                        empty = false;
+                        chunk.push(sample);
+                        if chunk.len() < chunk_len {
+                            continue;
+                        }
+
+                        callback(&chunk);
+                        chunk.clear();
+                        // 按实时速率回放
+                        next += chunk_duration;
+                        if let Some(wait) = next.checked_duration_since(Instant::now()) {
+                            std::thread::sleep(wait);
+                        }
+                        if stop_clone.load(Ordering::Relaxed) {
+                            return;
+                        }
+                    }
+
+                    if empty {
+                        error!("Input file: {path} is empty, stop replaying.");
+                        return;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for FileStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
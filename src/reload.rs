@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{Service, config::Config, task::Play};
+
+/// 运行中可直接应用的配置项，其余配置项修改后需重启生效
+///
+/// 音柱服务地址仅用于播放接口，通知订阅的连接仍需重启后切换
+const LIVE_KEYS: &[&str] = &[
+    "alarm.play_delay_secs",
+    "alarm.play_interval_secs",
+    "alarm.cycle_interval_secs",
+    "alarm.alarm_min_duration",
+    "alarm.test_min_duration",
+    "alarm.speech_min_duration",
+    "alarm.default_language",
+    "alarm.init_url",
+    "soundbox.alarm_media_path",
+    "soundbox.test_media_path",
+    "soundbox.broadcast_media_dir",
+    "soundpost.api_host",
+    "soundpost.api_login_token",
+    "soundpost.alarm_media_url",
+    "soundpost.test_media_url",
+    "soundpost.play_mode",
+    "tracing.level",
+];
+
+/// 修改日志级别
+pub type LevelSetter = Box<dyn Fn(&str) -> anyhow::Result<()> + Send + Sync>;
+
+/// 配置重新加载结果，仅包含配置项名称，不含配置值
+#[derive(Debug, Default, PartialEq)]
+pub struct ReloadReport {
+    /// 已应用的配置项
+    pub applied: Vec<String>,
+    /// 已修改但需重启生效的配置项
+    pub restart_required: Vec<String>,
+}
+
+/// 重新加载 config.toml 及环境变量覆盖，将可直接应用的配置应用到运行中的服务及播放器
+pub struct ConfigReloader {
+    path: String,
+    // 当前生效的配置，需重启的配置项保持启动时的值
+    running: BTreeMap<String, Value>,
+    service: Service,
+    play: Play,
+    set_level: Option<LevelSetter>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        path: String,
+        config: &Config,
+        service: Service,
+        play: Play,
+        set_level: Option<LevelSetter>,
+    ) -> Self {
+        Self {
+            path,
+            running: config.flatten(),
+            service,
+            play,
+            set_level,
+        }
+    }
+
+    pub async fn reload(&mut self) -> anyhow::Result<ReloadReport> {
+        let config = Config::new(&self.path)?;
+        let values = config.flatten();
+        let report = diff(&self.running, &values);
+
+        // 每次都重新加载媒体文件，文件内容可能在路径不变时被替换；加载失败时保持原配置
+        self.play.reload(&config)?;
+
+        {
+            let mut service = self.service.write().await;
+            service.play_delay_secs = config.alarm.play_delay_secs();
+            service.set_play_interval_secs(config.alarm.play_interval_secs());
+            service.set_cycle_interval_secs(config.alarm.cycle_interval_secs());
+            service.default_language = config.alarm.default_langauge();
+            service.alarms_init_url = config.alarm.init_url();
+        }
+
+        if report.applied.iter().any(|k| k == "tracing.level")
+            && let Some(set_level) = &self.set_level
+        {
+            set_level(&config.tracing.level())?;
+        }
+
+        for key in &report.applied {
+            self.running.insert(key.clone(), values[key].clone());
+            info!("Config reloaded, {key} applied");
+        }
+        for key in &report.restart_required {
+            warn!("Config reloaded, {key} changed, restart required");
+        }
+        Ok(report)
+    }
+}
+
+fn diff(running: &BTreeMap<String, Value>, values: &BTreeMap<String, Value>) -> ReloadReport {
+    let mut report = ReloadReport::default();
+    for (key, value) in values {
+        if running.get(key) == Some(value) {
+            continue;
+        }
+        if LIVE_KEYS.contains(&key.as_str()) {
+            report.applied.push(key.clone());
+        } else {
+            report.restart_required.push(key.clone());
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod reload_tests {
+    use crate::config::Config;
+
+    use super::{ReloadReport, diff};
+
+    #[test]
+    fn test_diff() {
+        let running = toml::from_str::<Config>("").unwrap().flatten();
+        let config: Config = toml::from_str(
+            "[alarm]\nplay_delay_secs = 3\n[mqtt]\nport = 8883\n[queue]\nbroadcast_size = 5",
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff(&running, &config.flatten()),
+            ReloadReport {
+                applied: vec!["alarm.play_delay_secs".to_string()],
+                restart_required: vec!["mqtt.port".to_string(), "queue.broadcast_size".to_string()],
+            }
+        );
+        assert_eq!(diff(&running, &running), ReloadReport::default());
+    }
+}
@@ -0,0 +1,340 @@
+use std::{fs, path::Path, str::FromStr};
+
+use cron::Schedule;
+use schemars::{JsonSchema, schema_for};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    handler::{AlarmConfirm, Command, FarmConfig, Soundposts},
+    model::{Alarm, Broadcast, TestAlarmConfig},
+    service::House,
+};
+
+/// 报警时间允许超前本地时间的上限，超出视为时钟异常
+const MAX_FUTURE_SKEW: Duration = Duration::minutes(5);
+
+/// 入站消息
+///
+/// 消息可携带 `version` 字段，缺省为 1；高于当前版本的消息直接拒绝，旧版本经兼容层转换后再反序列化及校验
+pub trait Inbound: DeserializeOwned + JsonSchema {
+    /// schema 名称，对应生成的文件名
+    const NAME: &'static str;
+    /// 当前 schema 版本
+    const VERSION: u32 = 1;
+
+    /// 兼容层: 将指定版本的消息转换为 Rust 类型对应的格式
+    fn normalize(_version: u32, value: Value) -> anyhow::Result<Value> {
+        Ok(value)
+    }
+
+    /// 指定版本的 JSON Schema
+    fn schema(_version: u32) -> Value {
+        schema_for!(Self).to_value()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// 解析并校验入站消息
+pub fn decode<T: Inbound>(payload: &[u8]) -> anyhow::Result<T> {
+    let mut value: Value = serde_json::from_slice(payload)?;
+
+    let version = match value.as_object_mut() {
+        Some(map) => match map.remove("version").or_else(|| map.remove("Version")) {
+            Some(v) => v
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid {} schema version: {v}", T::NAME))?,
+            None => 1,
+        },
+        None => 1,
+    };
+    if version == 0 || version > T::VERSION {
+        anyhow::bail!(
+            "Unsupported {} schema version: {version}, latest: {}",
+            T::NAME,
+            T::VERSION
+        );
+    }
+
+    let payload: T = serde_json::from_value(T::normalize(version, value)?)?;
+    payload.validate()?;
+
+    Ok(payload)
+}
+
+fn require(name: &str, value: &str) -> anyhow::Result<()> {
+    if value.trim().is_empty() {
+        anyhow::bail!("Field {name} must not be empty");
+    }
+    Ok(())
+}
+
+fn upper_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lower_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// 转换对象键名，如 camelCase -> PascalCase
+fn rename_keys(value: Value, rename: fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(map) => {
+            Value::Object(map.into_iter().map(|(k, v)| (rename(&k), v)).collect())
+        }
+        other => other,
+    }
+}
+
+impl Inbound for Alarm {
+    const NAME: &'static str = "alarm";
+    /// v1: PascalCase，v2: 与其他主题统一为 camelCase
+    const VERSION: u32 = 2;
+
+    fn normalize(version: u32, value: Value) -> anyhow::Result<Value> {
+        match version {
+            1 => Ok(value),
+            _ => Ok(rename_keys(value, upper_first)),
+        }
+    }
+
+    fn schema(version: u32) -> Value {
+        let mut schema = schema_for!(Self).to_value();
+        if version > 1
+            && let Some(map) = schema.as_object_mut()
+        {
+            if let Some(properties) = map.remove("properties") {
+                map.insert(
+                    "properties".to_string(),
+                    rename_keys(properties, lower_first),
+                );
+            }
+            if let Some(Value::Array(required)) = map.get_mut("required") {
+                for key in required.iter_mut() {
+                    if let Some(k) = key.as_str() {
+                        *key = json!(lower_first(k));
+                    }
+                }
+            }
+        }
+        schema
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        require("TargetName", &self.target_name)?;
+        require("AlarmItem", &self.alarm_item)?;
+        if self.timestamp > OffsetDateTime::now_utc() + MAX_FUTURE_SKEW {
+            anyhow::bail!("Alarm timestamp too far in the future: {}", self.timestamp);
+        }
+        Ok(())
+    }
+}
+
+impl Inbound for TestAlarmConfig {
+    const NAME: &'static str = "test_alarm_config";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.duration == 0 {
+            anyhow::bail!("Field duration must be greater than 0");
+        }
+        if let Some(crontab) = &self.crontab {
+            Schedule::from_str(crontab)
+                .map_err(|e| anyhow::anyhow!("Invalid crontab: {crontab}, err: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Inbound for FarmConfig {
+    const NAME: &'static str = "farm_config";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(lang) = &self.lang {
+            require("lang", lang)?;
+        }
+        Ok(())
+    }
+}
+
+impl Inbound for Soundposts {
+    const NAME: &'static str = "sound_posts";
+}
+
+impl Inbound for Vec<House> {
+    const NAME: &'static str = "houses";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for house in self {
+            require("code", &house.code)?;
+        }
+        Ok(())
+    }
+}
+
+impl Inbound for Vec<AlarmConfirm> {
+    const NAME: &'static str = "alarm_confirm";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for confirm in self {
+            require("houseCode", &confirm.house_code)?;
+            require("targetName", &confirm.target_name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Inbound for Broadcast {
+    const NAME: &'static str = "broadcast";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        Broadcast::validate(self)
+    }
+}
+
+impl Inbound for Command {
+    const NAME: &'static str = "command";
+
+    fn validate(&self) -> anyhow::Result<()> {
+        require("command", &self.command)
+    }
+}
+
+fn versioned<T: Inbound>(schemas: &mut Vec<(String, Value)>) {
+    for version in 1..=T::VERSION {
+        let mut schema = T::schema(version);
+        if let Some(map) = schema.as_object_mut() {
+            map.insert("$id".to_string(), json!(format!("{}.v{version}", T::NAME)));
+            // 数组消息无法携带版本
+            if let Some(Value::Object(properties)) = map.get_mut("properties") {
+                properties.insert(
+                    "version".to_string(),
+                    json!({ "type": "integer", "const": version }),
+                );
+                // 未携带版本视为 v1，新版本必须携带
+                if version > 1
+                    && let Some(Value::Array(required)) = map.get_mut("required")
+                {
+                    required.push(json!("version"));
+                }
+            }
+        }
+        schemas.push((format!("{}.v{version}.json", T::NAME), schema));
+    }
+}
+
+/// 全部入站消息的 JSON Schema，按文件名返回
+pub fn schemas() -> Vec<(String, Value)> {
+    let mut schemas = Vec::new();
+    versioned::<Alarm>(&mut schemas);
+    versioned::<TestAlarmConfig>(&mut schemas);
+    versioned::<FarmConfig>(&mut schemas);
+    versioned::<Soundposts>(&mut schemas);
+    versioned::<Vec<House>>(&mut schemas);
+    versioned::<Vec<AlarmConfirm>>(&mut schemas);
+    versioned::<Broadcast>(&mut schemas);
+    versioned::<Command>(&mut schemas);
+    schemas
+}
+
+/// 生成 JSON Schema 文件
+pub fn write_schemas(dir: &str) -> anyhow::Result<Vec<String>> {
+    fs::create_dir_all(dir)?;
+    let mut files = Vec::new();
+    for (name, schema) in schemas() {
+        let path = Path::new(dir).join(&name);
+        fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")?;
+        files.push(path.display().to_string());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+
+    use crate::model::{Alarm, TestAlarmConfig};
+
+    use super::{decode, schemas};
+
+    fn alarm_json(key: fn(&str) -> String, version: Option<u32>, ts: OffsetDateTime) -> String {
+        let mut map = serde_json::Map::new();
+        if let Some(v) = version {
+            map.insert("version".to_string(), v.into());
+        }
+        for (k, v) in [
+            ("TargetName", "温度"),
+            ("AlarmItem", "高温"),
+            ("Content", "1号舍高温"),
+            ("AlarmType", "alarm"),
+        ] {
+            map.insert(key(k), v.into());
+        }
+        map.insert(key("TimeStamp"), ts.format(&Rfc3339).unwrap().into());
+        map.insert(key("IsAlarm"), true.into());
+        serde_json::Value::Object(map).to_string()
+    }
+
+    fn pascal(k: &str) -> String {
+        k.to_string()
+    }
+
+    fn camel(k: &str) -> String {
+        k[..1].to_lowercase() + &k[1..]
+    }
+
+    #[test]
+    fn test_decode_alarm_versions() {
+        let now = OffsetDateTime::now_utc();
+        let v1 = decode::<Alarm>(alarm_json(pascal, None, now).as_bytes()).unwrap();
+        let v2 = decode::<Alarm>(alarm_json(camel, Some(2), now).as_bytes()).unwrap();
+        assert_eq!(v1.target_name, "温度");
+        assert_eq!(v2.target_name, "温度");
+        assert_eq!(v1.timestamp, v2.timestamp);
+
+        assert!(decode::<Alarm>(alarm_json(camel, Some(3), now).as_bytes()).is_err());
+        // 超出 u32 的版本号不截断
+        let overflow =
+            alarm_json(pascal, Some(1), now).replace(r#""version":1"#, r#""version":4294967297"#);
+        assert!(overflow.contains("4294967297"));
+        assert!(decode::<Alarm>(overflow.as_bytes()).is_err());
+        let future = now + Duration::hours(1);
+        assert!(decode::<Alarm>(alarm_json(pascal, Some(1), future).as_bytes()).is_err());
+
+        let empty = alarm_json(pascal, None, now).replace("温度", " ");
+        assert!(decode::<Alarm>(empty.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_decode_test_alarm_config() {
+        let ok = r#"{"duration":30,"crontab":"0 0 8 * * * *","playNow":false}"#;
+        assert!(decode::<TestAlarmConfig>(ok.as_bytes()).is_ok());
+        let bad = r#"{"duration":30,"crontab":"every day","playNow":false}"#;
+        assert!(decode::<TestAlarmConfig>(bad.as_bytes()).is_err());
+    }
+
+    /// 提交的 schema 文件需与代码一致，修改消息类型后执行 `alarm_player schemas` 重新生成
+    #[test]
+    fn test_schema_files_up_to_date() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/schema");
+        for (name, schema) in schemas() {
+            let path = format!("{dir}/{name}");
+            let content = std::fs::read_to_string(&path).unwrap();
+            let expected = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+            assert_eq!(content, expected, "{path} is outdated");
+        }
+    }
+}
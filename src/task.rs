@@ -1,8 +1,14 @@
 mod cycle;
 pub use cycle::Cycle;
 
+mod db_monitor;
+pub use db_monitor::DbMonitor;
+
+mod heartbeat;
+pub use heartbeat::{Heartbeat, HeartbeatData};
+
 mod play;
-pub use play::Play;
+pub use play::{Play, Playing};
 
 mod real_time;
 pub use real_time::RealTime;
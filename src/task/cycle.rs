@@ -12,24 +12,27 @@ use tracing::{error, info};
 use crate::{Service, model::Alarm, service::AlarmStatus};
 
 pub struct Cycle {
-    check_interval: u64,
     alarms: Mutex<VecDeque<Alarm>>,
     service: Service,
 }
 
 impl Cycle {
-    pub async fn init(check_interval: u64, service: Service) -> Self {
+    pub async fn init(service: Service) -> Self {
         let initial_alarms = {
             let service = service.read().await;
             service.get_alarms()
         };
         Self {
-            check_interval,
             alarms: Mutex::new(VecDeque::from(initial_alarms)),
             service,
         }
     }
 
+    // 循环间隔可热加载，每次从服务读取
+    async fn check_interval(&self) -> Duration {
+        Duration::from_secs(self.service.read().await.cycle_interval_secs)
+    }
+
     pub async fn run(&self, tx: Sender<Alarm>, mut rx: Receiver<Alarm>) {
         loop {
             tokio::select! {
@@ -59,7 +62,7 @@ impl Cycle {
         let alarm = match alarm {
             Some(alarm) => alarm,
             None => {
-                sleep(Duration::from_secs(self.check_interval)).await;
+                sleep(self.check_interval().await).await;
                 return;
             }
         };
@@ -75,7 +78,7 @@ impl Cycle {
                 return;
             }
             _ => {
-                sleep(Duration::from_secs(self.check_interval)).await;
+                sleep(self.check_interval().await).await;
 
                 info!("Send alarm to player: {:?}", alarm);
                 if let Err(e) = alarm_tx.send(alarm.clone()).await {
@@ -0,0 +1,173 @@
+use std::{sync::Arc, time::Duration};
+
+use sea_orm::DatabaseConnection;
+use tokio::{
+    sync::{Mutex, Notify, mpsc::Sender},
+    time::Instant,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    Service,
+    model::TestAlarmConfig,
+    service::{DbSnapshot, connect_db, setup_db},
+};
+
+/// 数据库连接监测
+///
+/// 定时检测连接，断开后按退避间隔重连；重连成功、定时或收到指令时重新加载数据库配置
+#[derive(Clone)]
+pub struct DbMonitor {
+    service: Service,
+    ct_tx: Sender<TestAlarmConfig>,
+    // 避免定时加载与指令加载并发执行
+    reloading: Arc<Mutex<()>>,
+}
+
+impl DbMonitor {
+    pub fn new(service: Service, ct_tx: Sender<TestAlarmConfig>) -> Self {
+        Self {
+            service,
+            ct_tx,
+            reloading: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub async fn run(&self, shutdown: Arc<Notify>) {
+        tokio::select! {
+            _ = shutdown.notified() => {
+                info!("Stop db monitor...");
+            },
+            _ = self.monitor() => {}
+        }
+    }
+
+    async fn monitor(&self) {
+        let (dbconfig, persist_enabled) = {
+            let service = self.service.read().await;
+            (service.dbconfig.clone(), service.persist.enabled())
+        };
+        let retry_min = Duration::from_secs(dbconfig.reconnect_min_secs().max(1));
+        let retry_max = Duration::from_secs(dbconfig.reconnect_max_secs()).max(retry_min);
+        let check_interval = Duration::from_secs(dbconfig.health_check_secs().max(1));
+        let reload_interval = match dbconfig.reload_interval_secs() {
+            0 => None,
+            // 下发的配置只在内存中，定时加载会将其还原为数据库配置
+            _ if !persist_enabled => {
+                warn!("MQTT config is not persisted, periodic db config reload disabled");
+                None
+            }
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        let mut retry = retry_min;
+        let mut last_reload = Instant::now();
+        loop {
+            let db = self.service.read().await.db.clone();
+            match db {
+                Some(db) => {
+                    tokio::time::sleep(check_interval).await;
+                    if let Err(e) = db.ping().await {
+                        warn!("Database connection lost: {e}");
+                        let mut service = self.service.write().await;
+                        service.set_db_error(&e.into());
+                        continue;
+                    }
+
+                    if reload_interval.is_some_and(|interval| last_reload.elapsed() >= interval) {
+                        last_reload = Instant::now();
+                        if let Err(e) = self.reload_with(db).await {
+                            warn!("Reload db config failed: {e}");
+                        }
+                    }
+                }
+                None => match self.reconnect().await {
+                    Ok(_) => {
+                        retry = retry_min;
+                        last_reload = Instant::now();
+                    }
+                    Err(e) => {
+                        warn!("Database reconnect failed: {e}, retry in {:?}", retry);
+                        {
+                            let mut service = self.service.write().await;
+                            service.set_db_error(&e);
+                        }
+                        tokio::time::sleep(retry).await;
+                        retry = (retry * 2).min(retry_max);
+                    }
+                },
+            }
+        }
+    }
+
+    /// 重连并重新加载鸡舍、鸡场、音柱及测试报警配置
+    async fn reconnect(&self) -> anyhow::Result<()> {
+        let dbconfig = self.service.read().await.dbconfig.clone();
+        // 连接及加载期间不持有服务锁，避免阻塞播放
+        let db = connect_db(&dbconfig).await?;
+        setup_db(&db, &dbconfig).await?;
+        self.apply(&db, true).await?;
+        info!("Database reconnected, config reloaded");
+        Ok(())
+    }
+
+    /// 重新加载数据库配置，返回配置变更
+    pub async fn reload(&self) -> anyhow::Result<Vec<String>> {
+        let db = self.service.read().await.db.clone();
+        match db {
+            Some(db) => self.reload_with(db).await,
+            None => anyhow::bail!("Database is not connected"),
+        }
+    }
+
+    async fn reload_with(&self, db: DatabaseConnection) -> anyhow::Result<Vec<String>> {
+        let changes = self.apply(&db, false).await?;
+        if changes.is_empty() {
+            info!("Db config reloaded, no changes");
+        }
+        Ok(changes)
+    }
+
+    /// 加载数据库配置并在一次写锁内整体应用
+    async fn apply(&self, db: &DatabaseConnection, connected: bool) -> anyhow::Result<Vec<String>> {
+        let _reloading = self.reloading.lock().await;
+        let mut snapshot = DbSnapshot::load(db).await?;
+
+        // 缓存数据库中的配置，不含本地状态文件覆盖的部分
+        let loaded = snapshot.clone();
+        let (changes, config, test_changed, cache_path) = {
+            let mut service = self.service.write().await;
+            if connected {
+                service.set_db(db.clone());
+            }
+            // 数据库模式下 MQTT 下发的配置写入前，数据库中的配置已过期，不覆盖内存中的配置
+            if service.persist.has_pending() {
+                warn!("MQTT config not yet written to db, db config reload skipped");
+                return Ok(Vec::new());
+            }
+            service.persist.overlay(&mut snapshot);
+            let old_test = service.test_alarm_snapshot();
+            let changes = service.apply_snapshot(&snapshot);
+            let config = TestAlarmConfig {
+                duration: service.get_test_play_duration(),
+                crontab: service.get_crontab(),
+                play_now: false,
+            };
+            let test_changed = service.test_alarm_snapshot() != old_test;
+            (changes, config, test_changed, service.dbconfig.cache_path())
+        };
+        loaded.write_cache(cache_path);
+        for change in &changes {
+            info!("Db config changed, {change}");
+        }
+
+        // 测试报警任务按新配置重新计划
+        if (connected && snapshot.test_alarm.is_some() || test_changed)
+            && let Err(e) = self.ct_tx.send(config).await
+        {
+            error!("Failed send test alarm config: {e}");
+        }
+
+        Ok(changes)
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use tokio::{sync::Notify, time::Instant};
+use tracing::{error, info};
+
+use crate::{
+    Service,
+    service::{AlarmService, DbHealth, OutputHealth},
+};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatData {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// 报警快照中未取消的报警数
+    pub active_alarms: usize,
+    /// 报警播放是否暂停
+    pub paused: bool,
+    /// 播放输出状态
+    pub output: OutputHealth,
+    /// 数据库连接状态
+    pub db: DbHealth,
+}
+
+impl HeartbeatData {
+    pub fn new(service: &AlarmService, uptime_secs: u64) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_secs,
+            active_alarms: service.alarm_set.len(),
+            paused: service.is_alarm_paused,
+            output: service.get_output_health(),
+            db: service.db_health.clone(),
+        }
+    }
+}
+
+/// 定时发布心跳，供看板判断播放器是否在线
+pub struct Heartbeat {
+    interval_secs: u64,
+    started: Instant,
+    service: Service,
+}
+
+impl Heartbeat {
+    pub fn new(interval_secs: u64, service: Service) -> Self {
+        Self {
+            interval_secs,
+            started: Instant::now(),
+            service,
+        }
+    }
+
+    pub async fn run(&self, shutdown: Arc<Notify>) {
+        tokio::select! {
+            _ = shutdown.notified() => {
+                info!("Stop heartbeat...");
+            },
+            _ = self.beat() => {}
+        }
+    }
+
+    async fn beat(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+
+            let data = {
+                let service = self.service.read().await;
+                HeartbeatData::new(&service, self.started.elapsed().as_secs())
+            };
+
+            match serde_json::to_string(&data) {
+                Ok(payload) => {
+                    // 心跳仅反映当前状态，断线期间不进入发件箱
+                    let service = self.service.read().await;
+                    service.publish_volatile(service.topics.player_heartbeat.clone(), payload);
+                }
+                Err(e) => error!("Heartbeat serialize failed: {e}"),
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::File,
+    path::Path,
+    sync::{Arc, RwLock},
+};
 
 use rodio::{Decoder, Source};
+use serde::Serialize;
 use time::{OffsetDateTime, PrimitiveDateTime};
 use tokio::sync::{
     Mutex,
@@ -10,25 +15,42 @@ use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use crate::{
-    Recorder, Service,
-    config::PlayMode,
-    model::Alarm,
+    RecordMeta, Recorder, Service,
+    config::{Config, PlayMode},
+    model::{Alarm, Broadcast, BroadcastPriority},
     player::{
-        Buffer, PlayCancelType, PlayContent, PlayResultType, Soundbox, Soundpost, SpeechLoop,
+        Buffer, PlayCancelType, PlayContent, PlayResultType, PlaySkipType, Soundbox, Soundpost,
+        SpeechLoop,
     },
-    service::{AlarmStatus, BoxConfig, PlayResult, PostConfig},
+    service::{AlarmService, AlarmStatus, BoxConfig, PlayResult, PostConfig},
 };
 
 #[derive(Default, Clone)]
 pub struct Tx {
     test_tx: Option<Sender<PlayCancelType>>,
     alarm_tx: Option<Sender<PlayCancelType>>,
+    broadcast_tx: Option<Sender<PlayCancelType>>,
+    // 当前广播为紧急广播时，报警到达不中断
+    broadcast_urgent: bool,
+}
+
+/// 当前播放内容
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playing {
+    /// alarm/test/broadcast
+    pub kind: &'static str,
+    pub house_code: String,
+    pub alarm_key: Option<String>,
+    pub content: String,
 }
 
+/// 可热加载的播放配置
 #[derive(Clone)]
-pub struct Play {
+struct PlaySettings {
     alarm_media_buffer: Buffer,
     test_media_buffer: Buffer,
+    broadcast_media_dir: String,
     alarm_media_url: String,
     test_media_url: String,
     alarm_min_duration: u64,
@@ -36,17 +58,46 @@ pub struct Play {
     speech_min_duration: u64,
     play_mode: PlayMode,
     soundpost: Soundpost,
+}
+
+impl PlaySettings {
+    fn new(config: &Config) -> anyhow::Result<Self> {
+        let (alarm_media_buffer, _) = Soundbox::load_media(&config.soundbox.alarm_media_path())?;
+        let (test_media_buffer, _) = Soundbox::load_media(&config.soundbox.test_media_path())?;
+        Ok(Self {
+            alarm_media_buffer,
+            test_media_buffer,
+            broadcast_media_dir: config.soundbox.broadcast_media_dir(),
+            alarm_media_url: config.soundpost.alarm_media_url(),
+            test_media_url: config.soundpost.test_media_url(),
+            alarm_min_duration: config.alarm.alarm_min_duration(),
+            test_min_duration: config.alarm.test_min_duration(),
+            speech_min_duration: config.alarm.speech_min_duration(),
+            play_mode: config.soundpost.play_mode(),
+            soundpost: Soundpost::new(
+                config.soundpost.api_host(),
+                config.soundpost.api_login_token(),
+            ),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Play {
+    settings: Arc<RwLock<PlaySettings>>,
     recorder: Recorder,
     service: Service,
     box_tx: Arc<Mutex<Tx>>,
     post_tx: Arc<Mutex<Tx>>,
     terminated: Arc<Mutex<bool>>,
+    playing: Arc<Mutex<Option<Playing>>>,
 }
 
 impl Play {
     pub fn new(
         alarm_media_path: String,
         test_media_path: String,
+        broadcast_media_dir: String,
         alarm_media_url: String,
         test_media_url: String,
         alarm_min_duration: u64,
@@ -56,10 +107,13 @@ impl Play {
         soundpost: Soundpost,
         recorder: Recorder,
         service: Service,
-    ) -> Self {
-        Self {
-            alarm_media_buffer: Self::get_buffer(alarm_media_path),
-            test_media_buffer: Self::get_buffer(test_media_path),
+    ) -> anyhow::Result<Self> {
+        let (alarm_media_buffer, _) = Soundbox::load_media(&alarm_media_path)?;
+        let (test_media_buffer, _) = Soundbox::load_media(&test_media_path)?;
+        let settings = PlaySettings {
+            alarm_media_buffer,
+            test_media_buffer,
+            broadcast_media_dir,
             alarm_media_url,
             test_media_url,
             alarm_min_duration,
@@ -67,17 +121,35 @@ impl Play {
             speech_min_duration,
             play_mode,
             soundpost,
+        };
+        Ok(Self {
+            settings: Arc::new(RwLock::new(settings)),
             recorder,
             service,
             box_tx: Default::default(),
             post_tx: Default::default(),
             terminated: Arc::new(Mutex::new(false)),
-        }
+            playing: Default::default(),
+        })
+    }
+
+    /// 按新配置重新加载媒体文件、最小播放时长、播放模式及音柱服务地址，进行中的播放不受影响
+    pub fn reload(&self, config: &Config) -> anyhow::Result<()> {
+        let settings = PlaySettings::new(config)?;
+        *self.settings.write().unwrap_or_else(|e| e.into_inner()) = settings;
+        Ok(())
+    }
+
+    /// 解析广播的 mediaFile，仅允许广播媒体目录下的文件
+    pub fn resolve_media_file(&self, media_file: &str) -> anyhow::Result<String> {
+        resolve_media_file(&self.settings().broadcast_media_dir, media_file)
     }
 
-    fn get_buffer(path: String) -> Buffer {
-        let file = File::open(path).unwrap();
-        Decoder::try_from(file).unwrap().buffered()
+    fn settings(&self) -> PlaySettings {
+        self.settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
     async fn cancel_test(&self, cancel_type: &PlayCancelType) {
@@ -124,19 +196,42 @@ impl Play {
         }
     }
 
+    async fn cancel_broadcast(&self, cancel_type: &PlayCancelType) {
+        for (name, tx) in [("box", &self.box_tx), ("post", &self.post_tx)] {
+            let mut tx = tx.lock().await;
+            if matches!(cancel_type, PlayCancelType::AlarmArrived) && tx.broadcast_urgent {
+                continue;
+            }
+            if let Some(tx) = tx.broadcast_tx.take() {
+                info!("Cancel {name} broadcast playing...");
+                if let Err(e) = tx.send(cancel_type.clone()).await {
+                    warn!("Failed for signaling by {name}.broadcast_tx: {:?}", e);
+                }
+            }
+        }
+    }
+
     async fn cancel(&self, cancel_type: PlayCancelType) {
         match cancel_type {
             PlayCancelType::AlarmArrived => {
                 self.cancel_test(&cancel_type).await;
+                self.cancel_broadcast(&cancel_type).await;
             }
             PlayCancelType::Terminated => {
                 self.cancel_test(&cancel_type).await;
+                self.cancel_broadcast(&cancel_type).await;
                 self.cancel_alarm(&cancel_type).await;
             }
         }
     }
 
+    /// 仅中断测试报警，用于广播到达
     pub async fn cancel_test_play(&self) {
+        self.cancel_test(&PlayCancelType::AlarmArrived).await;
+    }
+
+    /// 报警到达，中断测试报警及普通广播
+    pub async fn cancel_for_alarm(&self) {
         self.cancel(PlayCancelType::AlarmArrived).await;
     }
 
@@ -144,6 +239,26 @@ impl Play {
         self.cancel(PlayCancelType::Terminated).await;
     }
 
+    /// 远程停止当前播放
+    ///
+    /// 指定鸡舍或报警 key 时仅在当前播放匹配时停止，返回被停止的播放内容
+    pub async fn stop(&self, house_code: Option<&str>, alarm_key: Option<&str>) -> Option<Playing> {
+        let playing = self.playing.lock().await.clone()?;
+        let matched = house_code.is_none_or(|code| playing.house_code == code)
+            && alarm_key.is_none_or(|key| playing.alarm_key.as_deref() == Some(key));
+        if !matched {
+            return None;
+        }
+
+        info!("Stop playing: {:?}", playing);
+        self.cancel_play().await;
+        Some(playing)
+    }
+
+    async fn set_playing(&self, playing: Option<Playing>) {
+        *self.playing.lock().await = playing;
+    }
+
     pub async fn terminate_play(&self) {
         {
             let mut tm = self.terminated.lock().await;
@@ -158,6 +273,7 @@ impl Play {
         tx: Sender<Alarm>,
         mut realtime_rx: Receiver<Alarm>,
         mut cycle_rx: Receiver<Alarm>,
+        mut broadcast_rx: Receiver<Broadcast>,
     ) {
         loop {
             {
@@ -187,7 +303,14 @@ impl Play {
                         }
                     }
                 },
-                alarm = cycle_rx.recv(), if realtime_rx.is_empty() => {
+                broadcast = broadcast_rx.recv(), if realtime_rx.is_empty() => {
+                    if broadcast.is_none() {
+                        info!("Broadcast channel closed, exit play run...");
+                        return;
+                    }
+                    self.broadcast(broadcast.unwrap()).await;
+                },
+                alarm = cycle_rx.recv(), if realtime_rx.is_empty() && broadcast_rx.is_empty() => {
                     if alarm.is_none() {
                         info!("Cycle channel closed, exit play run...");
                         return;
@@ -216,6 +339,7 @@ impl Play {
     }
 
     async fn play(&self, alarm: Alarm) -> AlarmStatus {
+        let settings = self.settings();
         let alarm_status = {
             let service = self.service.read().await;
             service.get_alarm_status(&alarm)
@@ -253,6 +377,7 @@ impl Play {
             };
             let mut alarm = alarm.clone();
             alarm.test_time = Some(PrimitiveDateTime::new(local.date(), local.time()));
+            let play_content = settings.test_media_url.clone();
 
             let result = self
                 .play_test(
@@ -266,6 +391,8 @@ impl Play {
                 )
                 .await;
 
+            self.write_record_meta(&alarm, play_content, &result).await;
+
             let mut service = self.service.write().await;
             service.test_play_record(&alarm, result).await;
         };
@@ -273,10 +400,10 @@ impl Play {
         let play_alarm = async |alarm, box_config, posts_config| -> () {
             let (content, duration) = {
                 let service = self.service.read().await;
-                match self.play_mode {
+                match settings.play_mode {
                     PlayMode::Music => (
-                        PlayContent::Url(self.alarm_media_url.clone()),
-                        self.alarm_min_duration,
+                        PlayContent::Url(settings.alarm_media_url.clone()),
+                        settings.alarm_min_duration,
                     ),
                     PlayMode::Tts => {
                         let content = match service.get_alarm_content(&alarm) {
@@ -286,11 +413,16 @@ impl Play {
                                 return;
                             }
                         };
-                        (PlayContent::Tts(content), self.speech_min_duration)
+                        (PlayContent::Tts(content), settings.speech_min_duration)
                     }
                 }
             };
 
+            let play_content = match &content {
+                PlayContent::Tts(text) => text.clone(),
+                PlayContent::Url(url) => url.clone(),
+            };
+
             let result = self
                 .play_alarm(
                     box_config,
@@ -303,6 +435,8 @@ impl Play {
                     },
                 )
                 .await;
+            self.write_record_meta(&alarm, play_content, &result).await;
+
             {
                 let mut service = self.service.write().await;
                 service.play_record(&alarm, result).await;
@@ -317,38 +451,143 @@ impl Play {
                 info!("Alarm was paused, don't play, continue...");
             }
             AlarmStatus::Playable => {
+                self.set_playing(Some(Playing {
+                    kind: if alarm.is_test { "test" } else { "alarm" },
+                    house_code: alarm.house_code.clone(),
+                    alarm_key: Some(AlarmService::get_alarm_set_key(&alarm)),
+                    content: alarm.content.clone(),
+                }))
+                .await;
                 if alarm.is_test {
                     play_test_alarm(box_config.clone(), posts_config.clone()).await;
                 } else {
                     info!("Play alarm: {:?}", alarm);
                     play_alarm(alarm.clone(), box_config, posts_config).await;
                 }
+                self.set_playing(None).await;
             }
         }
 
         alarm_status
     }
 
+    async fn broadcast(&self, broadcast: Broadcast) {
+        let settings = self.settings();
+        let (ongoing, box_config, mut posts_config) = {
+            let service = self.service.read().await;
+            (
+                service.is_ongoing_alarm_exist(),
+                service.get_soundbox(),
+                service.get_soundposts(),
+            )
+        };
+
+        // 文字及媒体地址仅音柱可播放，本地文件仅音箱可播放
+        let content = match (&broadcast.text, &broadcast.media_url) {
+            (Some(text), _) => Some(PlayContent::Tts(text.clone())),
+            (None, Some(url)) => Some(PlayContent::Url(url.clone())),
+            (None, None) => None,
+        };
+        if content.is_none() {
+            posts_config.device_ids.clear();
+        } else if let Some(device_ids) = &broadcast.device_ids {
+            posts_config.device_ids.retain(|id| device_ids.contains(id));
+        }
+        let sbox = BoxConfig {
+            enabled: box_config.enabled && broadcast.soundbox && broadcast.media_file.is_some(),
+            ..box_config
+        };
+
+        let skipped = if broadcast.priority == BroadcastPriority::Normal && ongoing {
+            Some((
+                PlaySkipType::AlarmOngoing,
+                "Alarm ongoing, broadcast skipped",
+            ))
+        } else if !sbox.enabled && posts_config.device_ids.is_empty() {
+            Some((PlaySkipType::NoOutput, "No output available for broadcast"))
+        } else {
+            None
+        };
+        if let Some((skip_type, message)) = skipped {
+            warn!("{message}: {:?}", broadcast);
+            let now = Self::now();
+            let result = PlayResult {
+                id: Self::get_record_id(),
+                record_file: String::new(),
+                has_error: true,
+                err_message: Some(message.to_string()),
+                play_type: None,
+                result_type: PlayResultType::Skipped(skip_type),
+                level: None,
+                devices: Vec::new(),
+                start_time: now,
+                stop_time: now,
+            };
+            let mut service = self.service.write().await;
+            service.broadcast_record(&broadcast, result).await;
+            return;
+        }
+
+        let min_duration = match content {
+            Some(PlayContent::Tts(_)) => settings.speech_min_duration,
+            _ => settings.alarm_min_duration,
+        };
+        let gap = 2;
+        let duration = broadcast.duration_secs.unwrap_or(
+            min_duration * broadcast.repeat as u64 + gap * (broadcast.repeat as u64 - 1),
+        );
+
+        info!("Play broadcast: {:?}", broadcast);
+        self.set_playing(Some(Playing {
+            kind: "broadcast",
+            house_code: broadcast.houses.join(","),
+            alarm_key: None,
+            content: broadcast.content(),
+        }))
+        .await;
+        let result = self
+            .play_broadcast(
+                sbox,
+                posts_config,
+                &broadcast,
+                content,
+                SpeechLoop {
+                    duration,
+                    times: broadcast.repeat,
+                    gap,
+                },
+            )
+            .await;
+        self.set_playing(None).await;
+        self.write_broadcast_meta(&broadcast, &result).await;
+
+        let mut service = self.service.write().await;
+        service.broadcast_record(&broadcast, result).await;
+    }
+
     async fn play_test(
         &self,
         sbox: BoxConfig,
         posts: PostConfig,
         speech_loop: SpeechLoop,
     ) -> PlayResult {
+        let settings = self.settings();
         let id = Self::get_record_id();
-        let filename = format!("{}.wav", id);
+        let filename = self.recorder.file_name(&id);
+        let devices = Self::get_devices(&sbox, &posts);
+        let start_time = Self::now();
 
         let mut play_type = None;
         let record = self
             .recorder
-            .start(filename)
+            .start(filename.clone())
             .inspect_err(|e| error!("Recorder start failed: {e}"));
         let mut js = tokio::task::JoinSet::new();
         if sbox.enabled {
             play_type = Some("音箱报警".to_string());
-            let audio_data = self.test_media_buffer.clone();
+            let audio_data = settings.test_media_buffer.clone();
             let sl = speech_loop.clone();
-            let duration = self.test_min_duration;
+            let duration = settings.test_min_duration;
             let (tx, rx) = mpsc::channel(1);
             {
                 let mut box_tx = self.box_tx.lock().await;
@@ -366,8 +605,8 @@ impl Play {
                 None => Some("音箱报警".to_string()),
             };
             let device_ids = posts.device_ids;
-            let content = PlayContent::Url(self.test_media_url.clone());
-            let soundpost = self.soundpost.clone();
+            let content = PlayContent::Url(settings.test_media_url.clone());
+            let soundpost = settings.soundpost.clone();
             let (tx, rx) = mpsc::channel(1);
             {
                 let mut post_tx = self.post_tx.lock().await;
@@ -404,21 +643,30 @@ impl Play {
 
         debug!("playing task finished, write record...");
 
+        let mut level = None;
         if let Ok((stream, writer)) = record {
-            let _ = self
+            level = self
                 .recorder
                 .stop(stream, writer)
-                .inspect_err(|e| error!("Close record writer failed: {e}"));
+                .await
+                .inspect(|level| info!("Record level: {level}"))
+                .inspect_err(|e| error!("Close record writer failed: {e}"))
+                .ok();
         }
 
         debug!("Recorder stopped, playing task finished!");
 
         PlayResult {
             id,
+            record_file: filename,
             has_error,
             err_message,
             play_type,
             result_type,
+            level,
+            devices,
+            start_time,
+            stop_time: Self::now(),
         }
     }
 
@@ -429,21 +677,24 @@ impl Play {
         content: PlayContent,
         speech_loop: SpeechLoop,
     ) -> PlayResult {
+        let settings = self.settings();
         let id = Self::get_record_id();
 
-        let filename = format!("{}.wav", id);
+        let filename = self.recorder.file_name(&id);
+        let devices = Self::get_devices(&sbox, &posts);
+        let start_time = Self::now();
         let record = self
             .recorder
-            .start(filename)
+            .start(filename.clone())
             .inspect_err(|e| error!("Recorder start failed: {e}"));
 
         let mut play_type = None;
         let mut js = tokio::task::JoinSet::new();
         if sbox.enabled {
             play_type = Some("音箱报警".to_string());
-            let audio_data = self.alarm_media_buffer.clone();
+            let audio_data = settings.alarm_media_buffer.clone();
             let sl = speech_loop.clone();
-            let duration = self.alarm_min_duration;
+            let duration = settings.alarm_min_duration;
             let (tx, rx) = mpsc::channel(1);
             {
                 let mut box_tx = self.box_tx.lock().await;
@@ -461,7 +712,7 @@ impl Play {
                 None => Some("音柱报警".to_string()),
             };
             let device_ids = posts.device_ids.clone();
-            let speed = match self.play_mode {
+            let speed = match settings.play_mode {
                 PlayMode::Tts => Some(posts.speed),
                 PlayMode::Music => None,
             };
@@ -470,7 +721,7 @@ impl Play {
                 let mut post_tx = self.post_tx.lock().await;
                 post_tx.alarm_tx = Some(tx);
             }
-            let soundpost = self.soundpost.clone();
+            let soundpost = settings.soundpost.clone();
             js.spawn(async move {
                 soundpost
                     .play(device_ids, content, speed, speech_loop, rx)
@@ -500,25 +751,257 @@ impl Play {
             }
         }
 
+        let mut level = None;
+        if let Ok((stream, writer)) = record {
+            level = self
+                .recorder
+                .stop(stream, writer)
+                .await
+                .inspect(|level| info!("Record level: {level}"))
+                .inspect_err(|e| error!("Close record writer failed: {e}"))
+                .ok();
+        }
+
+        PlayResult {
+            id,
+            record_file: filename,
+            has_error,
+            play_type,
+            err_message,
+            result_type,
+            level,
+            devices,
+            start_time,
+            stop_time: Self::now(),
+        }
+    }
+
+    async fn play_broadcast(
+        &self,
+        sbox: BoxConfig,
+        posts: PostConfig,
+        broadcast: &Broadcast,
+        content: Option<PlayContent>,
+        speech_loop: SpeechLoop,
+    ) -> PlayResult {
+        let settings = self.settings();
+        let id = Self::get_record_id();
+
+        let filename = self.recorder.file_name(&id);
+        let devices = Self::get_devices(&sbox, &posts);
+        let start_time = Self::now();
+        let record = self
+            .recorder
+            .start(filename.clone())
+            .inspect_err(|e| error!("Recorder start failed: {e}"));
+
+        let urgent = broadcast.priority == BroadcastPriority::Urgent;
+        let mut play_type = None;
+        let mut js = tokio::task::JoinSet::new();
+        if let (true, Some(media_file)) = (sbox.enabled, broadcast.media_file.clone()) {
+            play_type = Some("音箱广播".to_string());
+            let sl = speech_loop.clone();
+            let (tx, rx) = mpsc::channel(1);
+            {
+                let mut box_tx = self.box_tx.lock().await;
+                box_tx.broadcast_tx = Some(tx);
+                box_tx.broadcast_urgent = urgent;
+            }
+            js.spawn(async move {
+                let audio_data = Decoder::try_from(File::open(media_file)?)?.buffered();
+                let sb = Soundbox::new(1);
+                sb.play(audio_data, sl, rx).await
+            });
+        }
+
+        if let (false, Some(content)) = (posts.device_ids.is_empty(), content) {
+            play_type = match play_type {
+                Some(_) => Some("音柱音箱".to_string()),
+                None => Some("音柱广播".to_string()),
+            };
+            let device_ids = posts.device_ids.clone();
+            let speed = match content {
+                PlayContent::Tts(_) => Some(posts.speed),
+                PlayContent::Url(_) => None,
+            };
+            let (tx, rx) = mpsc::channel(1);
+            {
+                let mut post_tx = self.post_tx.lock().await;
+                post_tx.broadcast_tx = Some(tx);
+                post_tx.broadcast_urgent = urgent;
+            }
+            let soundpost = settings.soundpost.clone();
+            js.spawn(async move {
+                soundpost
+                    .play(device_ids, content, speed, speech_loop, rx)
+                    .await
+            });
+        }
+
+        let mut has_error = false;
+        let mut err_message: Option<String> = None;
+        let mut result_type = PlayResultType::Normal;
+        debug!("waitting for broadcast playing task to complete...");
+        while let Some(res) = js.join_next().await {
+            match res {
+                Ok(Ok(t)) => {
+                    result_type = t;
+                }
+                Ok(Err(e)) => {
+                    error!("Task failed: {e}");
+                    err_message = Some(e.to_string());
+                    has_error = true;
+                }
+                Err(e) => {
+                    error!("Task failed: {e}");
+                    err_message = Some(e.to_string());
+                    has_error = true;
+                }
+            }
+        }
+
+        let mut level = None;
         if let Ok((stream, writer)) = record {
-            let _ = self
+            level = self
                 .recorder
                 .stop(stream, writer)
-                .inspect_err(|e| error!("Close record writer failed: {e}"));
+                .await
+                .inspect(|level| info!("Record level: {level}"))
+                .inspect_err(|e| error!("Close record writer failed: {e}"))
+                .ok();
         }
 
         PlayResult {
             id,
+            record_file: filename,
             has_error,
             play_type,
             err_message,
             result_type,
+            level,
+            devices,
+            start_time,
+            stop_time: Self::now(),
         }
     }
 
     fn get_record_id() -> String {
         Uuid::new_v4().to_string()
     }
+
+    fn get_devices(sbox: &BoxConfig, posts: &PostConfig) -> Vec<String> {
+        let mut devices = Vec::new();
+        if sbox.enabled {
+            devices.push("soundbox".to_string());
+        }
+        for id in posts.device_ids.iter() {
+            devices.push(format!("soundpost:{id}"));
+        }
+        devices
+    }
+
+    fn now() -> OffsetDateTime {
+        match OffsetDateTime::now_local() {
+            Ok(local) => local,
+            Err(e) => {
+                error!("Failed to get local time: {e}");
+                OffsetDateTime::now_utc()
+            }
+        }
+    }
+
+    /// 写入录音元数据，录音失败时不写
+    async fn write_record_meta(&self, alarm: &Alarm, play_content: String, result: &PlayResult) {
+        if result.level.is_none() {
+            return;
+        }
+
+        let house_name = {
+            let service = self.service.read().await;
+            service
+                .house_set
+                .get(&alarm.house_code)
+                .map(|house| house.name.clone())
+        };
+
+        let meta = RecordMeta {
+            id: result.id.clone(),
+            record_file: result.record_file.clone(),
+            alarm_key: AlarmService::get_alarm_set_key(alarm),
+            house_code: alarm.house_code.clone(),
+            house_name,
+            content: alarm.content.clone(),
+            play_content,
+            is_test: alarm.is_test,
+            devices: result.devices.clone(),
+            play_type: result.play_type.clone(),
+            result: format!("{:?}", result.result_type),
+            has_error: result.has_error,
+            err_message: result.err_message.clone(),
+            start_time: result.start_time,
+            stop_time: result.stop_time,
+            level: result.level.clone(),
+        };
+
+        if let Err(e) = self.recorder.write_meta(&meta) {
+            error!("Write record meta failed: {e}");
+        }
+    }
+
+    /// 写入广播录音元数据，录音失败时不写
+    async fn write_broadcast_meta(&self, broadcast: &Broadcast, result: &PlayResult) {
+        if result.level.is_none() {
+            return;
+        }
+
+        let house_name = {
+            let service = self.service.read().await;
+            let names: Vec<String> = broadcast
+                .houses
+                .iter()
+                .filter_map(|code| service.house_set.get(code))
+                .map(|house| house.name.clone())
+                .collect();
+            (!names.is_empty()).then(|| names.join(","))
+        };
+
+        let meta = RecordMeta {
+            id: result.id.clone(),
+            record_file: result.record_file.clone(),
+            alarm_key: "broadcast".to_string(),
+            house_code: broadcast.houses.join(","),
+            house_name,
+            content: broadcast.content(),
+            play_content: broadcast.content(),
+            is_test: false,
+            devices: result.devices.clone(),
+            play_type: result.play_type.clone(),
+            result: format!("{:?}", result.result_type),
+            has_error: result.has_error,
+            err_message: result.err_message.clone(),
+            start_time: result.start_time,
+            stop_time: result.stop_time,
+            level: result.level.clone(),
+        };
+
+        if let Err(e) = self.recorder.write_meta(&meta) {
+            error!("Write record meta failed: {e}");
+        }
+    }
+}
+
+fn resolve_media_file(dir: &str, media_file: &str) -> anyhow::Result<String> {
+    let dir = Path::new(dir)
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Broadcast media dir: {dir} not available: {e}"))?;
+    let path = dir
+        .join(media_file)
+        .canonicalize()
+        .map_err(|e| anyhow::anyhow!("Broadcast media file: {media_file} not found: {e}"))?;
+    if !path.starts_with(&dir) || !path.is_file() {
+        anyhow::bail!("Broadcast media file: {media_file} is not allowed");
+    }
+    Ok(path.to_string_lossy().into_owned())
 }
 
 #[cfg(test)]
@@ -529,13 +1012,13 @@ mod play_tests {
     use tracing::info;
 
     use crate::{
-        config::{DbConfig, PlayMode},
+        config::{DbConfig, PlayMode, RecorderConfig},
         player::{PlayContent, Soundpost, SpeechLoop},
         recorder::Recorder,
         service::{AlarmService, PostConfig},
     };
 
-    use super::Play;
+    use super::{Play, resolve_media_file};
 
     fn create_play() -> Play {
         let test_media_name = "resource/please-calm-my-mind-125566.wav".to_string();
@@ -549,7 +1032,9 @@ mod play_tests {
             "YWRtaW46YWRtaW5fYXBpX2tleQ==".into(),
         );
 
-        let recorder = Recorder::new("/tmp".to_string(), "/tmp".to_string());
+        let config: RecorderConfig =
+            toml::from_str("record_storage_path = \"/tmp\"\nrecord_link_path = \"/tmp\"").unwrap();
+        let recorder = Recorder::new(config);
         let mut service = AlarmService::new(
             5,
             "zh_CN".to_string(),
@@ -567,6 +1052,7 @@ mod play_tests {
         Play::new(
             alarm_media_name,
             test_media_name,
+            "./resource".to_string(),
             alarm_media_url,
             test_media_url,
             30,
@@ -577,6 +1063,25 @@ mod play_tests {
             recorder,
             Arc::new(RwLock::new(service)),
         )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_media_file() {
+        let dir = std::env::temp_dir().join("broadcast_media");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/notice.wav"), "").unwrap();
+        let media_dir = dir.join("sub");
+        let media_dir = media_dir.to_str().unwrap();
+
+        let path = resolve_media_file(media_dir, "notice.wav").unwrap();
+        assert!(path.ends_with("sub/notice.wav"));
+        std::fs::write(dir.join("outside.wav"), "").unwrap();
+        assert!(resolve_media_file(media_dir, "../outside.wav").is_err());
+        assert!(resolve_media_file(media_dir, "/etc/passwd").is_err());
+        assert!(resolve_media_file(media_dir, "not-exists.wav").is_err());
+        std::fs::remove_dir_all(dir).unwrap();
     }
 
     #[tokio::test]
@@ -616,8 +1121,8 @@ mod play_tests {
 
     #[tokio::test]
     async fn test_play_alarm() {
-        let mut play = create_play();
-        play.play_mode = PlayMode::Tts;
+        let play = create_play();
+        play.settings.write().unwrap().play_mode = PlayMode::Tts;
         let box_config = {
             let service = play.service.read().await;
             service.get_soundbox()
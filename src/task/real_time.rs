@@ -4,7 +4,10 @@ use time::OffsetDateTime;
 use tokio::sync::mpsc::{Receiver, Sender, error::TryRecvError};
 use tracing::{error, info};
 
-use crate::{Service, model::Alarm};
+use crate::{
+    Service,
+    model::{Alarm, alarm_event_history::AlarmEventType},
+};
 
 pub struct RealTime {
     service: Service,
@@ -44,12 +47,17 @@ impl RealTime {
                         continue;
                     }
 
-                    let play_delay = {
+                    let play_time = {
                         let service = self.service.read().await;
-                        service.get_play_delay()
+                        let play_time = alarm_time.saturating_add(service.get_play_delay());
+                        service.record_event(
+                            &alarm,
+                            AlarmEventType::Queued,
+                            Some(format!("play at {play_time}")),
+                        );
+                        play_time
                     };
 
-                    let play_time = alarm_time.saturating_add(play_delay);
                     let current_time = OffsetDateTime::now_utc();
                     if play_time > OffsetDateTime::now_utc() {
                         let delay =
@@ -7,7 +7,7 @@ use tokio::net::TcpStream;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
 use tracing::{debug, error, info, warn};
 
-use crate::{Service, TOPIC_SOUNDPOST_STATUS};
+use crate::Service;
 
 #[derive(Clone, Deserialize)]
 pub struct LoginResult {
@@ -40,10 +40,23 @@ impl WsClient {
         password: String,
         service: Service,
     ) -> anyhow::Result<Self> {
-        let client = reqwest::Client::new();
+        let token = Self::login(&api_host, &username, &password).await?;
+
+        Ok(Self {
+            api_host,
+            token,
+            service,
+        })
+    }
+
+    /// 登录音柱服务，返回访问令牌
+    pub async fn login(api_host: &str, username: &str, password: &str) -> anyhow::Result<String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
         let mut request_data = HashMap::new();
-        request_data.insert("username", username.as_str());
-        request_data.insert("password", password.as_str());
+        request_data.insert("username", username);
+        request_data.insert("password", password);
         let result: LoginResponse = client
             .post(format!("http://{}/v1/login", api_host))
             .json(&request_data)
@@ -53,16 +66,39 @@ impl WsClient {
             .await?;
 
         if result.code != StatusCode::OK {
-            return anyhow::bail!("Login failed: {}", result.message);
+            anyhow::bail!("Login failed: {}", result.message);
         }
 
-        let token = result.value.unwrap().token;
+        match result.value {
+            Some(value) => Ok(value.token),
+            None => anyhow::bail!("Login failed: no token returned"),
+        }
+    }
 
-        Ok(Self {
-            api_host,
-            token,
-            service,
-        })
+    /// 登录失败时按间隔重试，直到登录成功
+    pub async fn connect(
+        api_host: String,
+        username: String,
+        password: String,
+        service: Service,
+    ) -> Self {
+        let retry_interval = Duration::from_secs(5);
+        loop {
+            match Self::new(
+                api_host.clone(),
+                username.clone(),
+                password.clone(),
+                service.clone(),
+            )
+            .await
+            {
+                Ok(client) => return client,
+                Err(e) => {
+                    error!("Soundpost login failed: {e}, retry in {:?}", retry_interval);
+                    tokio::time::sleep(retry_interval).await;
+                }
+            }
+        }
     }
 
     pub async fn subscribe(&self, shutdown: Arc<tokio::sync::Notify>) {
@@ -130,7 +166,8 @@ impl WsClient {
                         };
                         if event.event == "onlineStatus" {
                             let mut service = self.service.write().await;
-                            service.publish(TOPIC_SOUNDPOST_STATUS, text).await;
+                            let topic = service.topics.soundpost_status.clone();
+                            service.publish(topic, text).await;
                         }
                     }
                     Message::Ping(data) => {
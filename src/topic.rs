@@ -0,0 +1,212 @@
+use crate::config::TopicConfig;
+
+/// MQTT 主题集合，由主题配置的前缀模板生成
+#[derive(Debug, Clone)]
+pub struct Topics {
+    // 共享订阅组，为空时不使用共享订阅
+    share_group: Option<String>,
+    /// 报警: `{prefix}/{house_code}/+/alarm`
+    pub alarm: String,
+    /// 重发报警: `{prefix}/{house_code}/+/repub_alarms`
+    pub repub_alarm: String,
+    /// {"duration": 120, "crontab": "0 12 * * * * *", "playNow": false}
+    pub crontab: String,
+    /// {"code": 0, "message": "Success", "data": {"planTime": "", "testTime": "", "result": 3}}
+    pub crontab_result: String,
+    /// {"device_id": 1, "status": "online"}
+    pub soundpost_status: String,
+    /// {"pause": true, "lang": "zh_Hans", "enableBox": true}
+    pub farm_config: String,
+    /// 鸡场配置处理失败回复: {"code": 1, "message": "..", "data": null}
+    pub farm_config_result: String,
+    /// {"deviceIds": [1,  2], "speed": 50}
+    pub sound_posts: String,
+    /// [{"name": "9200", "code": "h42k3433", "enabled": true, "isEmptyMode": false}, ..]
+    pub houses: String,
+    /// [{"houseCode": "d2123sd333", "targetName": "高温报警", "isConfirmed": true}]
+    pub alarm_confirm: String,
+    /// 报警确认处理失败回复: {"code": 1, "message": "..", "data": null}
+    pub alarm_confirm_result: String,
+    /// {"status": "online"}，保留消息，掉线时由遗嘱消息置为 offline
+    pub player_status: String,
+    /// {"version": "0.1.0", "uptimeSecs": 60, "activeAlarms": 1, "paused": false, "output": {..}}
+    pub player_heartbeat: String,
+    /// 运行时查询指令 {"command": "get_status"}，响应主题及关联数据遵循 MQTT5
+    pub command: String,
+    /// 未指定响应主题时的指令回复主题
+    pub command_result: String,
+    /// 临时广播通知
+    pub broadcast: String,
+    pub broadcast_result: String,
+    /// {"topic": "..", "payload": "..", "error": "..", "timestamp": ".."}
+    pub dead_letter: Option<String>,
+}
+
+impl Default for Topics {
+    fn default() -> Self {
+        Self::build(
+            "",
+            Some("ap".to_string()),
+            Some("ap/dead_letter".to_string()),
+        )
+    }
+}
+
+impl Topics {
+    pub fn new(config: &TopicConfig) -> anyhow::Result<Self> {
+        let mut prefix = config.prefix();
+        for (name, value) in [("tenant", config.tenant()), ("farm", config.farm())] {
+            let placeholder = format!("{{{name}}}");
+            if !prefix.contains(&placeholder) {
+                continue;
+            }
+            match value {
+                Some(value) => prefix = prefix.replace(&placeholder, &value),
+                None => anyhow::bail!("Topic prefix: {prefix} requires `{name}` configured."),
+            }
+        }
+
+        if prefix.contains(['{', '}']) {
+            anyhow::bail!("Topic prefix: {prefix} contains unknown placeholder.");
+        }
+        if prefix.contains(['+', '#']) {
+            anyhow::bail!("Topic prefix: {prefix} must not contain wildcards.");
+        }
+
+        Ok(Self::build(
+            prefix.trim_matches('/'),
+            config.share_group(),
+            config.dead_letter(),
+        ))
+    }
+
+    fn build(prefix: &str, share_group: Option<String>, dead_letter: Option<String>) -> Self {
+        let topic = |name: &str| {
+            if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{prefix}/{name}")
+            }
+        };
+
+        Self {
+            share_group,
+            alarm: topic("+/+/alarm"),
+            repub_alarm: topic("+/+/repub_alarms"),
+            crontab: topic("ap/test_alarm/crontab"),
+            crontab_result: topic("ap/test_alarm/crontab/result"),
+            soundpost_status: topic("ap/soundpost/status"),
+            farm_config: topic("ap/alarm/farm_config"),
+            farm_config_result: topic("ap/alarm/farm_config/result"),
+            sound_posts: topic("ap/device/sound_posts"),
+            houses: topic("ap/alarm/houses"),
+            alarm_confirm: topic("ap/alarm/confirm"),
+            alarm_confirm_result: topic("ap/alarm/confirm/result"),
+            player_status: topic("ap/player/status"),
+            player_heartbeat: topic("ap/player/heartbeat"),
+            command: topic("ap/player/command"),
+            command_result: topic("ap/player/command/result"),
+            broadcast: topic("ap/player/broadcast"),
+            broadcast_result: topic("ap/player/broadcast/result"),
+            dead_letter: dead_letter.map(|name| topic(&name)),
+        }
+    }
+
+    /// 生成共享订阅主题，多个播放器实例分担消息
+    pub fn shared(&self, filter: &str) -> String {
+        match self.share_group.as_ref() {
+            Some(group) => format!("$share/{group}/{filter}"),
+            None => filter.to_string(),
+        }
+    }
+}
+
+/// 按 MQTT 通配规则匹配主题，返回 `+`/`#` 匹配到的层级
+///
+/// 共享订阅前缀 `$share/{group}/` 会被忽略，`$` 开头的主题不匹配首层通配符
+pub fn matches<'a>(filter: &str, topic: &'a str) -> Option<Vec<&'a str>> {
+    let filter = match filter.strip_prefix("$share/") {
+        Some(shared) => shared.split_once('/')?.1,
+        None => filter,
+    };
+
+    if topic.starts_with('$') && filter.starts_with(['+', '#']) {
+        return None;
+    }
+
+    let mut captures = Vec::new();
+    let mut levels = topic.split('/');
+    for (i, f) in filter.split('/').enumerate() {
+        if f == "#" {
+            // `#` 同时匹配父级，如 `a/#` 匹配 `a`
+            captures.push(topic.splitn(i + 1, '/').nth(i).unwrap_or(""));
+            return Some(captures);
+        }
+
+        let level = levels.next()?;
+        match f {
+            "+" => captures.push(level),
+            _ if f == level => {}
+            _ => return None,
+        }
+    }
+
+    match levels.next() {
+        Some(_) => None,
+        None => Some(captures),
+    }
+}
+
+#[cfg(test)]
+mod topic_tests {
+    use crate::config::TopicConfig;
+
+    use super::{Topics, matches};
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(matches("+/+/alarm", "h1/d1/alarm"), Some(vec!["h1", "d1"]));
+        assert_eq!(
+            matches("$share/ap/+/+/alarm", "h1/d1/alarm"),
+            Some(vec!["h1", "d1"])
+        );
+        assert_eq!(matches("+/+/alarm", "h1/d1/false_alarm"), None);
+        assert_eq!(matches("+/+/alarm", "h1/alarm"), None);
+        assert_eq!(matches("+/+/alarm", "t/f/h1/d1/alarm"), None);
+        assert_eq!(
+            matches("ap/alarm/confirm", "ap/alarm/confirm"),
+            Some(vec![])
+        );
+        assert_eq!(
+            matches("ap/#", "ap/alarm/confirm"),
+            Some(vec!["alarm/confirm"])
+        );
+        assert_eq!(matches("ap/#", "ap"), Some(vec![""]));
+        assert_eq!(matches("#", "$SYS/broker/uptime"), None);
+        assert_eq!(matches("+/+/alarm", "$SYS/d1/alarm"), None);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let config: TopicConfig =
+            toml::from_str("prefix = \"{tenant}/{farm}\"\ntenant = \"t1\"\nfarm = \"f1\"").unwrap();
+        let topics = Topics::new(&config).unwrap();
+        assert_eq!(topics.farm_config, "t1/f1/ap/alarm/farm_config");
+        assert_eq!(topics.dead_letter.as_deref(), Some("t1/f1/ap/dead_letter"));
+        assert_eq!(topics.shared(&topics.alarm), "$share/ap/t1/f1/+/+/alarm");
+        assert_eq!(
+            matches(&topics.alarm, "t1/f1/h1/d1/alarm"),
+            Some(vec!["h1", "d1"])
+        );
+        assert_eq!(matches(&topics.alarm, "t1/f2/h1/d1/alarm"), None);
+
+        let config: TopicConfig = toml::from_str("prefix = \"{tenant}/{farm}\"").unwrap();
+        assert!(Topics::new(&config).is_err());
+
+        let config: TopicConfig = toml::from_str("share_group = \"\"\ndead_letter = \"\"").unwrap();
+        let topics = Topics::new(&config).unwrap();
+        assert_eq!(topics.shared(&topics.alarm), "+/+/alarm");
+        assert_eq!(topics.crontab, "ap/test_alarm/crontab");
+        assert_eq!(topics.dead_letter, None);
+    }
+}